@@ -0,0 +1,101 @@
+use crate::camera::*;
+use crate::*;
+use cgmath::prelude::*;
+
+/// how a keyframe moves on to the next one
+#[derive(Clone, Copy, Debug)]
+pub enum Interpolation {
+    Linear,
+    /// the Catmull-Rom spline, through all the keyframes
+    Spline,
+}
+
+impl Interpolation {
+    pub fn from_name(name: &str) -> Result<Interpolation, String> {
+        match name {
+            "linear" => Ok(Interpolation::Linear),
+            "spline" => Ok(Interpolation::Spline),
+            _ => Err(format!("unknown interpolation `{}`", name)),
+        }
+    }
+}
+
+/// the parameters of a perspective camera at a frame
+#[derive(Clone, Copy)]
+pub struct Keyframe {
+    pub frame: i32,
+    pub lookfrom: Pt3,
+    pub lookat: Pt3,
+    pub vup: Vec3,
+    pub vfov: f32,
+    /// the interpolation to the next keyframe
+    pub interpolation: Interpolation,
+}
+
+/// the keyframes of the camera, sorted by the frame
+pub struct CameraTrack {
+    keys: Vec<Keyframe>,
+}
+
+// see: https://en.wikipedia.org/wiki/Centripetal_Catmull%E2%80%93Rom_spline
+// the uniform one is used, the keyframes are usually evenly spaced
+fn catmull_rom(p0: Vec3, p1: Vec3, p2: Vec3, p3: Vec3, t: f32) -> Vec3 {
+    let (t2, t3) = (t * t, t * t * t);
+    0.5 * ((2.0 * p1)
+        + (p2 - p0) * t
+        + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+        + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3)
+}
+
+impl CameraTrack {
+    /// `keys` should not be empty
+    pub fn new(mut keys: Vec<Keyframe>) -> Self {
+        keys.sort_by_key(|k| k.frame);
+        CameraTrack { keys }
+    }
+
+    /// the parameters at the frame, the first and the last keyframes hold out of the track
+    pub fn at(&self, frame: i32) -> Keyframe {
+        let keys = &self.keys;
+        let i = match keys.iter().rposition(|k| k.frame <= frame) {
+            Some(i) => i,
+            None => return keys[0],
+        };
+        if i + 1 == keys.len() {
+            return keys[i];
+        }
+        let (k1, k2) = (&keys[i], &keys[i + 1]);
+        let t = (frame - k1.frame) as f32 / (k2.frame - k1.frame) as f32;
+        let k0 = &keys[i.saturating_sub(1)];
+        let k3 = &keys[(i + 2).min(keys.len() - 1)];
+        let lerp = |f: &dyn Fn(&Keyframe) -> Vec3| match k1.interpolation {
+            Interpolation::Linear => f(k1).lerp(f(k2), t),
+            Interpolation::Spline => catmull_rom(f(k0), f(k1), f(k2), f(k3), t),
+        };
+        let vfov = lerp(&|k| Vec3::new(k.vfov, 0.0, 0.0)).x;
+        Keyframe {
+            frame,
+            lookfrom: Pt3::from_vec(lerp(&|k| k.lookfrom.to_vec())),
+            lookat: Pt3::from_vec(lerp(&|k| k.lookat.to_vec())),
+            vup: lerp(&|k| k.vup).normalize(),
+            vfov,
+            interpolation: k1.interpolation,
+        }
+    }
+
+    /// the camera at the frame, with the aperture and its shape, the lens effects and the shutter of `base`,
+    /// focused at `lookat`
+    pub fn camera(&self, frame: i32, base: &dyn Camera, aspect: f32) -> Box<dyn Camera> {
+        let k = self.at(frame);
+        let mut cam = PerspectiveCamera::new(k.lookfrom, k.lookat, k.vup, k.vfov, aspect);
+        if let Some(lens) = base.lens() {
+            cam.lens.radius = lens.radius;
+            cam.lens.shape = lens.shape.clone();
+        }
+        if let Some(effects) = base.effects() {
+            cam.effects = *effects;
+        }
+        cam.shutter = base.shutter();
+        Box::new(cam)
+    }
+}
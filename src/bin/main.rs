@@ -1,63 +1,88 @@
-use ray_tracer::sampler::*;
+use ray_tracer::camera::Camera;
+use ray_tracer::denoise::Denoiser;
+use ray_tracer::image::*;
+use ray_tracer::render::{output_image, render_view};
 use ray_tracer::scene::Scene;
-use ray_tracer::shader::*;
-use ray_tracer::*;
+use ray_tracer::settings::*;
+use ray_tracer::stereo::Eye;
+use ray_tracer::tonemap::ToneMapper;
 use std::env;
-use std::fs::File;
-use std::io::*;
-use std::time::*;
+use std::process;
 
-fn main() -> obj::ObjResult<()> {
-    let now = Instant::now();
-    let args: Vec<String> = env::args().collect();
-    let parse_to_io_err = |_| Error::new(ErrorKind::Other, "parse int error");
-    // let pixel_samper = args[1].parse::<i32>().map_err(parse_to_io_err).and_then(SamplerKind::from_int)?;
-    let light_samper = args[1]
-        .parse::<i32>()
-        .map_err(parse_to_io_err)
-        .and_then(SamplerKind::from_int)?;
-
-    // let mut scene = Scene::light_test(light_samper);
-    // let mut scene = Scene::mis_test(light_samper);
-    let mut scene = Scene::cornell_box(light_samper)?;
-    // let scene = Scene::blue_noise_test();
-    let mut output = File::create("./output/out.ppm")?;
-    // let mut output = File::create("./trash/out.ppm")?;
-
-    writeln!(&mut output, "P3\n{} {}\n255", NX, NY)?;
-    for j in (0..NY).rev() {
-        for i in 0..NX {
-            let mut col = Vec3::new(0.0, 0.0, 0.0);
-            let mut sampler = BlueNoiseSampler::new(1.0, NS, false);
-            // let mut sampler = JitteredSampler::new(1.0, NS);
-            // let mut sampler = UniformSampler::new(1.0, NS);
-            // let mut sampler = WhiteNoiseSampler::new(1.0, NS);
-            while let Some((a, b)) = sampler.sample() {
-                let u = (i as f32 + a) / NX as f32;
-                let v = (j as f32 + b) / NY as f32;
+fn main() {
+    let settings = match Settings::from_args(env::args().skip(1)) {
+        Ok(Some(s)) => s,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return;
+        }
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            process::exit(1);
+        }
+    };
+    if let Err(e) = run(&settings) {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
 
-                let r = scene.cam.get_ray(u, v);
-                // col += normal_shader(&r, &scene.world);
-                // col += whitted_trace_shader(&r, &mut scene.world, 0);
-                // col += path_trace_shader(&r, &mut scene.world, 0);
-                col += path_trace_shader_mis(&r, &mut scene.world, 0);
+fn run(settings: &Settings) -> Result<(), Box<dyn std::error::Error>> {
+    // check the output format before the long rendering
+    Format::from_path(&settings.output)?;
+    if let Some(path) = &settings.heatmap {
+        Format::from_path(path)?;
+    }
+    let mut scene = Scene::load(settings)?;
+    match settings.frames {
+        Some((first, last)) => {
+            for frame in first..=last {
+                println!("Frame {} of {}-{}", frame, first, last);
+                let settings = settings.for_frame(frame);
+                scene.set_frame(frame, &settings)?;
+                render_frame(&scene, &settings)?;
             }
-            col = &col / NS as f32;
-            col = Vec3::new(col.x.sqrt(), col.y.sqrt(), col.z.sqrt());
-
-            let ir = (255.99 * col.x) as i32;
-            let ig = (255.99 * col.y) as i32;
-            let ib = (255.99 * col.z) as i32;
-            writeln!(&mut output, "{} {} {}", ir, ig, ib)?;
-        }
-        if j % 5 == 0 {
-            println!(
-                "Now rendering: {}%, {} seconds elapsed",
-                ((NY - j) * 100) as f32 / NY as f32,
-                now.elapsed().as_secs()
-            );
         }
+        None => render_frame(&scene, settings)?,
     }
     println!("Finished.");
     Ok(())
 }
+
+fn render_frame(scene: &Scene, settings: &Settings) -> Result<(), Box<dyn std::error::Error>> {
+    let image = match &settings.stereo {
+        Some(rig) => {
+            let mut eyes = Vec::new();
+            for eye in [Eye::Left, Eye::Right] {
+                println!("The {} eye", eye.name());
+                let settings = settings.for_eye(eye);
+                let cam = rig.eye(eye, scene.cam.as_ref())?;
+                eyes.push(render_image(scene, &cam, &settings)?);
+            }
+            settings.stereo_layout.combine(&eyes[0], &eyes[1])
+        }
+        None => render_image(scene, scene.cam.as_ref(), settings)?,
+    };
+    write_image(&settings.output, &image, &settings.tone_mapper)?;
+    Ok(())
+}
+
+/// render the image seen by the camera, and write its heatmap
+fn render_image(
+    scene: &Scene,
+    cam: &dyn Camera,
+    settings: &Settings,
+) -> Result<Image, Box<dyn std::error::Error>> {
+    let film = render_view(scene, cam, settings)?;
+    if let Some(path) = &settings.heatmap {
+        let heatmap = output_image(&film, settings, |film| film.heatmap(settings.spp as u32));
+        write_image(path, &heatmap, &ToneMapper::default())?;
+    }
+    Ok(output_image(&film, settings, |film| {
+        if settings.denoise {
+            Denoiser::default().denoise(&film.image(), &film.features())
+        } else {
+            film.image()
+        }
+    }))
+}
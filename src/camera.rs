@@ -1,16 +1,322 @@
+use crate::hitable::*;
+use crate::image::{read_png, Image};
 use crate::ray::*;
+use crate::sampler::*;
+use crate::shader::World;
+use crate::tonemap::luminance;
 use crate::*;
 use cgmath::prelude::*;
+use std::f32::consts::PI;
+use std::io;
+use std::sync::Arc;
 
-pub struct Camera {
+/// cameras are shared by all the rendering threads
+/// (s, t) is the texture coordinate of the image, from the bottom-left corner
+pub trait Camera: Send + Sync {
+    fn get_ray(&self, s: f32, t: f32) -> Ray;
+
+    /// the ray through the point `lens` of the lens, given in the unit disk
+    fn get_ray_lens(&self, s: f32, t: f32, _lens: (f32, f32)) -> Ray {
+        self.get_ray(s, t)
+    }
+
+    /// the kind of the camera
+    fn name(&self) -> &'static str;
+
+    /// where the camera is
+    fn eye(&self) -> Pt3;
+
+    /// where the camera looks at, normalized
+    fn view_dir(&self) -> Vec3;
+
+    /// the up direction of the image, normalized
+    fn up(&self) -> Vec3;
+
+    /// the rays of `get_ray` are at the time the shutter opens
+    fn shutter(&self) -> Shutter;
+
+    fn shutter_mut(&mut self) -> &mut Shutter;
+
+    /// whether (s, t) is covered by the projection, the uncovered pixels stay black
+    fn in_view(&self, _s: f32, _t: f32) -> bool {
+        true
+    }
+
+    /// the thin lens, if the camera has one
+    fn lens(&self) -> Option<&ThinLens> {
+        None
+    }
+
+    fn lens_mut(&mut self) -> Option<&mut ThinLens> {
+        None
+    }
+
+    /// the lens effects, if the camera may have some
+    fn effects(&self) -> Option<&LensEffects> {
+        None
+    }
+
+    fn effects_mut(&mut self) -> Option<&mut LensEffects> {
+        None
+    }
+
+    /// the shape the points of the lens are sampled in, `None` for a pinhole
+    fn aperture(&self) -> Option<&ApertureShape> {
+        self.lens().filter(|l| l.radius > 0.0).map(|l| &l.shape)
+    }
+
+    /// the ray of `get_ray_lens` for the channel 0, 1 or 2 of the color, through the lens effects,
+    /// with the fraction of the light reaching the film
+    fn get_ray_channel(&self, s: f32, t: f32, lens: (f32, f32), _channel: usize) -> (Ray, f32) {
+        (self.get_ray_lens(s, t, lens), 1.0)
+    }
+}
+
+impl dyn Camera {
+    /// focus on whatever the center ray hits, and return the focus distance
+    /// the focus distance is kept if the center ray hits nothing, or there is no lens
+    pub fn autofocus(&mut self, world: &World) -> Option<f32> {
+        let r = self.get_ray(0.5, 0.5);
+        let rec = world.objects.hit(&r, T_MIN, T_MAX)?;
+        let dist = (rec.p - self.eye()).dot(self.view_dir());
+        let lens = self.lens_mut()?;
+        lens.focus_dist = dist;
+        Some(dist)
+    }
+}
+
+/// the interval the shutter is open, the rays are spread over it for the motion blur
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Shutter {
+    pub open: f32,
+    pub close: f32,
+}
+
+impl Shutter {
+    /// the time at `k` in [0, 1) of the interval
+    pub fn time(&self, k: f32) -> f32 {
+        self.open + k * (self.close - self.open)
+    }
+}
+
+/// a thin lens, all the rays through a point on the film meet at the plane in focus
+#[derive(Clone)]
+pub struct ThinLens {
+    /// a pinhole if it is 0
+    pub radius: f32,
+    /// the distance from the lens to the plane in focus, along the view direction
+    pub focus_dist: f32,
+    /// the shape of the aperture, the out of focus highlights take it
+    pub shape: ApertureShape,
+}
+
+/// the shape of the aperture, in the square [-1, 1]^2 scaled by the radius of the lens
+#[derive(Clone)]
+pub enum ApertureShape {
+    Circle,
+    /// a regular polygon of `blades` sides inscribed in the unit circle,
+    /// rotated by `rotation` degrees
+    Polygon {
+        blades: u32,
+        rotation: f32,
+    },
+    Mask(Arc<ApertureMask>),
+    /// the whole square, for the cameras mapping it to their own lens
+    Square,
+}
+
+/// a grayscale image of the aperture, the light passing through each point in [0, 1]
+pub struct ApertureMask {
+    image: Image,
+    /// the mean of the mask, the fraction of the light passing through
+    coverage: f32,
+}
+
+impl ApertureMask {
+    /// read the mask from a PNG image, which spans the square of the aperture
+    pub fn load(path: &str) -> io::Result<ApertureMask> {
+        let image = read_png(path)?;
+        let coverage =
+            image.pixels.iter().map(|c| luminance(*c)).sum::<f32>() / image.pixels.len() as f32;
+        if coverage <= 0.0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("the aperture mask {} is black", path),
+            ));
+        }
+        Ok(ApertureMask { image, coverage })
+    }
+
+    /// the light passing through (x, y) of the square [-1, 1]^2, from the bottom-left corner
+    fn transmittance(&self, x: f32, y: f32) -> f32 {
+        let (w, h) = (self.image.width, self.image.height);
+        let i = ((0.5 * (x + 1.0) * w as f32) as i32).clamp(0, w - 1);
+        let j = ((0.5 * (1.0 - y) * h as f32) as i32).clamp(0, h - 1);
+        luminance(self.image.get(i, j))
+    }
+}
+
+impl ApertureShape {
+    /// whether the point (x, y) of the square [-1, 1]^2 passes through the aperture,
+    /// randomly for the partly transparent points of a mask
+    pub fn accept(&self, x: f32, y: f32) -> bool {
+        match self {
+            ApertureShape::Circle => x * x + y * y < 1.0,
+            ApertureShape::Polygon { blades, rotation } => {
+                // the distance to the edge of the sector the point is in
+                let sector = 2.0 * PI / *blades as f32;
+                let phi = (y.atan2(x) - rotation.to_radians()).rem_euclid(sector);
+                let edge = (0.5 * sector).cos() / (phi - 0.5 * sector).cos();
+                x * x + y * y < edge * edge
+            }
+            ApertureShape::Mask(mask) => rand::random::<f32>() < mask.transmittance(x, y),
+            ApertureShape::Square => true,
+        }
+    }
+
+    /// the fraction of the square [-1, 1]^2 the aperture covers
+    pub fn coverage(&self) -> f32 {
+        match self {
+            ApertureShape::Circle => 0.25 * PI,
+            ApertureShape::Polygon { blades, .. } => {
+                let n = *blades as f32;
+                0.125 * n * (2.0 * PI / n).sin()
+            }
+            ApertureShape::Mask(mask) => mask.coverage,
+            ApertureShape::Square => 1.0,
+        }
+    }
+}
+
+/// an endless sequence of the points of the aperture in [-1, 1]^2, for the samples of a pixel
+pub enum ApertureSequence<'a> {
+    Disk(SampleSequence),
+    /// the samples of the square, rejected out of the shape
+    Shape(&'a ApertureShape, SampleSequence),
+}
+
+impl<'a> ApertureSequence<'a> {
+    /// about `rate` points of `kind` in the aperture, the same ones for the same `seed`
+    pub fn new(shape: &'a ApertureShape, kind: SamplerKind, rate: i32, seed: u64) -> Self {
+        match shape {
+            ApertureShape::Circle => {
+                ApertureSequence::Disk(SampleSequence::new(kind, 2.0, rate, true, seed))
+            }
+            _ => {
+                // a square number, which the stratified samplers keep in full
+                let edge = (rate as f32 / shape.coverage()).sqrt().ceil() as i32;
+                let rate = edge * edge;
+                ApertureSequence::Shape(shape, SampleSequence::new(kind, 2.0, rate, false, seed))
+            }
+        }
+    }
+
+    pub fn sample(&mut self) -> (f32, f32) {
+        let (x, y) = match self {
+            ApertureSequence::Disk(disk) => disk.sample(),
+            ApertureSequence::Shape(shape, square) => loop {
+                let (x, y) = square.sample();
+                if shape.accept(x - 1.0, y - 1.0) {
+                    break (x, y);
+                }
+            },
+        };
+        (x - 1.0, y - 1.0)
+    }
+
+    /// skip the first `n` points, taken by the earlier passes
+    pub fn skip(&mut self, n: usize) {
+        match self {
+            ApertureSequence::Disk(disk) => disk.skip(n),
+            ApertureSequence::Shape(..) => {
+                for _ in 0..n {
+                    self.sample();
+                }
+            }
+        }
+    }
+}
+
+/// the imperfections of a real lens, in the coordinates of the image plane at distance 1
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LensEffects {
+    /// the Brown-Conrady radial distortion coefficients k1, k2, k3,
+    /// negative for the barrel distortion, positive for the pincushion one
+    pub radial: [f32; 3],
+    /// the tangential distortion coefficients p1, p2
+    pub tangential: [f32; 2],
+    /// the strength of the cos^4 falloff, 1 for the natural vignetting
+    pub vignetting: f32,
+    /// the lateral chromatic aberration, the red rays spread wider than the green ones
+    /// by this fraction, the blue ones narrower
+    pub chromatic: f32,
+}
+
+// see: https://en.wikipedia.org/wiki/Distortion_(optics)#Software_correction
+impl LensEffects {
+    pub fn is_none(&self) -> bool {
+        *self == LensEffects::default()
+    }
+
+    /// where the point (x, y) of the rectilinear projection is imaged
+    pub fn distort(&self, x: f32, y: f32) -> (f32, f32) {
+        let [k1, k2, k3] = self.radial;
+        let [p1, p2] = self.tangential;
+        let r2 = x * x + y * y;
+        let radial = 1.0 + r2 * (k1 + r2 * (k2 + r2 * k3));
+        (
+            x * radial + 2.0 * p1 * x * y + p2 * (r2 + 2.0 * x * x),
+            y * radial + p1 * (r2 + 2.0 * y * y) + 2.0 * p2 * x * y,
+        )
+    }
+
+    /// the inverse of `distort`, by the fixed-point iteration,
+    /// `None` out of the image of the lens, where the iteration does not converge
+    pub fn undistort(&self, xd: f32, yd: f32) -> Option<(f32, f32)> {
+        let [k1, k2, k3] = self.radial;
+        let [p1, p2] = self.tangential;
+        let (mut x, mut y) = (xd, yd);
+        for _ in 0..20 {
+            let r2 = x * x + y * y;
+            let radial = 1.0 + r2 * (k1 + r2 * (k2 + r2 * k3));
+            let dx = 2.0 * p1 * x * y + p2 * (r2 + 2.0 * x * x);
+            let dy = p1 * (r2 + 2.0 * y * y) + 2.0 * p2 * x * y;
+            x = (xd - dx) / radial;
+            y = (yd - dy) / radial;
+        }
+        let (ex, ey) = self.distort(x, y);
+        let err = ((ex - xd).powi(2) + (ey - yd).powi(2)).sqrt();
+        (err < 1e-3 * (1.0 + xd.abs() + yd.abs())).then_some((x, y))
+    }
+
+    /// the scale of the image plane for the channel 0, 1 or 2 of the color
+    pub fn channel_scale(&self, channel: usize) -> f32 {
+        1.0 + self.chromatic * (1.0 - channel as f32)
+    }
+
+    /// the fraction of the light reaching the point (x, y) of the image plane
+    pub fn vignetting(&self, x: f32, y: f32) -> f32 {
+        let cos2 = 1.0 / (1.0 + x * x + y * y);
+        1.0 - self.vignetting * (1.0 - cos2 * cos2)
+    }
+}
+
+pub struct PerspectiveCamera {
     origin: Pt3,
     lower_left_corner: Pt3,
     horizontal: Vec3,
     vertical: Vec3,
+    /// the basis of the camera, `w` points backwards
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+    pub lens: ThinLens,
+    pub shutter: Shutter,
+    pub effects: LensEffects,
 }
 
-impl Camera {
-    pub fn new(lookfrom: Pt3, lookat: Pt3, vup: Vec3, vfov: f32, aspect: f32) -> Camera {
+impl PerspectiveCamera {
+    pub fn new(lookfrom: Pt3, lookat: Pt3, vup: Vec3, vfov: f32, aspect: f32) -> PerspectiveCamera {
         let theta = vfov.to_radians();
         let half_height = (theta / 2.0).tan();
         let half_width = aspect * half_height;
@@ -18,18 +324,475 @@ impl Camera {
         let w = (lookfrom - lookat).normalize();
         let u = vup.cross(w).normalize();
         let v = w.cross(u);
-        Camera {
-            origin: origin,
-            lower_left_corner: Pt3::new(-half_width, -half_height, -1.0),
+        PerspectiveCamera {
+            origin,
+            // the image plane is at the distance 1 in front of the camera
+            lower_left_corner: origin - half_width * u - half_height * v - w,
             horizontal: 2.0 * half_width * u,
             vertical: 2.0 * half_height * v,
+            u,
+            v,
+            w,
+            lens: ThinLens {
+                radius: 0.0,
+                focus_dist: (lookat - lookfrom).magnitude(),
+                shape: ApertureShape::Circle,
+            },
+            shutter: Shutter::default(),
+            effects: LensEffects::default(),
         }
     }
+}
 
-    pub fn get_ray(&self, s: f32, t: f32) -> Ray {
+impl Camera for PerspectiveCamera {
+    /// the ray of the pinhole camera
+    fn get_ray(&self, s: f32, t: f32) -> Ray {
         Ray {
             o: self.origin,
             d: self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin,
+            time: self.shutter.open,
+        }
+    }
+
+    /// all the rays of (s, t) meet at the plane in focus, where the pinhole ray crosses it
+    fn get_ray_lens(&self, s: f32, t: f32, lens: (f32, f32)) -> Ray {
+        let r = self.get_ray(s, t);
+        if self.lens.radius <= 0.0 {
+            return r;
+        }
+        let focus = r.point_at_parameter(self.lens.focus_dist / r.d.dot(-self.w));
+        let o = self.origin + self.lens.radius * (lens.0 * self.u + lens.1 * self.v);
+        Ray {
+            o,
+            d: focus - o,
+            time: r.time,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "perspective"
+    }
+
+    fn eye(&self) -> Pt3 {
+        self.origin
+    }
+
+    fn view_dir(&self) -> Vec3 {
+        -self.w
+    }
+
+    fn up(&self) -> Vec3 {
+        self.v
+    }
+
+    fn shutter(&self) -> Shutter {
+        self.shutter
+    }
+
+    fn shutter_mut(&mut self) -> &mut Shutter {
+        &mut self.shutter
+    }
+
+    fn lens(&self) -> Option<&ThinLens> {
+        Some(&self.lens)
+    }
+
+    fn lens_mut(&mut self) -> Option<&mut ThinLens> {
+        Some(&mut self.lens)
+    }
+
+    fn effects(&self) -> Option<&LensEffects> {
+        Some(&self.effects)
+    }
+
+    fn effects_mut(&mut self) -> Option<&mut LensEffects> {
+        Some(&mut self.effects)
+    }
+
+    /// (s, t) is where the ray is imaged through the distortion,
+    /// the image plane is scaled for the channel before it is undistorted
+    fn get_ray_channel(&self, s: f32, t: f32, lens: (f32, f32), channel: usize) -> (Ray, f32) {
+        let e = &self.effects;
+        if e.is_none() {
+            return (self.get_ray_lens(s, t, lens), 1.0);
         }
+        let (hw, hh) = (
+            0.5 * self.horizontal.magnitude(),
+            0.5 * self.vertical.magnitude(),
+        );
+        let k = e.channel_scale(channel);
+        match e.undistort(k * (2.0 * s - 1.0) * hw, k * (2.0 * t - 1.0) * hh) {
+            Some((x, y)) => {
+                let r = self.get_ray_lens(0.5 + 0.5 * x / hw, 0.5 + 0.5 * y / hh, lens);
+                (r, e.vignetting(x, y))
+            }
+            // no light reaches the film out of the image of the lens
+            None => (self.get_ray_lens(s, t, lens), 0.0),
+        }
+    }
+}
+
+/// the parallel projection, for the technical and architectural views
+pub struct OrthographicCamera {
+    /// the center of the view
+    origin: Pt3,
+    horizontal: Vec3,
+    vertical: Vec3,
+    v: Vec3,
+    w: Vec3,
+    pub shutter: Shutter,
+}
+
+impl OrthographicCamera {
+    /// `height` is the height of the view in the world space
+    pub fn new(
+        lookfrom: Pt3,
+        lookat: Pt3,
+        vup: Vec3,
+        height: f32,
+        aspect: f32,
+    ) -> OrthographicCamera {
+        let w = (lookfrom - lookat).normalize();
+        let u = vup.cross(w).normalize();
+        let v = w.cross(u);
+        OrthographicCamera {
+            origin: lookfrom,
+            horizontal: height * aspect * u,
+            vertical: height * v,
+            v,
+            w,
+            shutter: Shutter::default(),
+        }
+    }
+}
+
+impl Camera for OrthographicCamera {
+    fn get_ray(&self, s: f32, t: f32) -> Ray {
+        Ray {
+            o: self.origin + (s - 0.5) * self.horizontal + (t - 0.5) * self.vertical,
+            d: -self.w,
+            time: self.shutter.open,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "orthographic"
+    }
+
+    fn eye(&self) -> Pt3 {
+        self.origin
+    }
+
+    fn view_dir(&self) -> Vec3 {
+        -self.w
+    }
+
+    fn up(&self) -> Vec3 {
+        self.v
+    }
+
+    fn shutter(&self) -> Shutter {
+        self.shutter
+    }
+
+    fn shutter_mut(&mut self) -> &mut Shutter {
+        &mut self.shutter
+    }
+}
+
+/// the orthonormal basis of a camera, `w` points backwards
+#[derive(Clone, Copy)]
+struct Basis {
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+}
+
+impl Basis {
+    fn new(lookfrom: Pt3, lookat: Pt3, vup: Vec3) -> Self {
+        let w = (lookfrom - lookat).normalize();
+        let u = vup.cross(w).normalize();
+        let v = w.cross(u);
+        Basis { u, v, w }
+    }
+}
+
+/// the latitude-longitude projection of the full sphere,
+/// the longitude goes along s with the view direction at the center, the latitude along t
+pub struct EquirectangularCamera {
+    origin: Pt3,
+    basis: Basis,
+    pub shutter: Shutter,
+}
+
+impl EquirectangularCamera {
+    pub fn new(lookfrom: Pt3, lookat: Pt3, vup: Vec3) -> EquirectangularCamera {
+        EquirectangularCamera {
+            origin: lookfrom,
+            basis: Basis::new(lookfrom, lookat, vup),
+            shutter: Shutter::default(),
+        }
+    }
+}
+
+impl Camera for EquirectangularCamera {
+    fn get_ray(&self, s: f32, t: f32) -> Ray {
+        let phi = (s - 0.5) * 2.0 * PI;
+        let theta = (t - 0.5) * PI;
+        let Basis { u, v, w } = self.basis;
+        Ray {
+            o: self.origin,
+            d: theta.cos() * (phi.sin() * u - phi.cos() * w) + theta.sin() * v,
+            time: self.shutter.open,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "equirectangular"
+    }
+
+    fn eye(&self) -> Pt3 {
+        self.origin
+    }
+
+    fn view_dir(&self) -> Vec3 {
+        -self.basis.w
+    }
+
+    fn up(&self) -> Vec3 {
+        self.basis.v
+    }
+
+    fn shutter(&self) -> Shutter {
+        self.shutter
+    }
+
+    fn shutter_mut(&mut self) -> &mut Shutter {
+        &mut self.shutter
+    }
+}
+
+/// how the angle from the view direction maps to the distance from the image center
+#[derive(Clone, Copy, Debug)]
+pub enum FisheyeMapping {
+    /// the distance is proportional to the angle
+    Equidistant,
+    /// the distance is proportional to the sine of half the angle, keeping the solid angle
+    Equisolid,
+}
+
+impl FisheyeMapping {
+    pub fn from_name(name: &str) -> Result<FisheyeMapping, String> {
+        match name {
+            "equidistant" => Ok(FisheyeMapping::Equidistant),
+            "equisolid" => Ok(FisheyeMapping::Equisolid),
+            _ => Err(format!("unknown fisheye mapping `{}`", name)),
+        }
+    }
+}
+
+/// a fisheye lens, the image circle is inscribed in the image
+pub struct FisheyeCamera {
+    origin: Pt3,
+    basis: Basis,
+    /// the field of view of the image circle, in radians
+    fov: f32,
+    mapping: FisheyeMapping,
+    aspect: f32,
+    pub shutter: Shutter,
+}
+
+impl FisheyeCamera {
+    /// `fov` is the field of view of the image circle, in degrees
+    pub fn new(
+        lookfrom: Pt3,
+        lookat: Pt3,
+        vup: Vec3,
+        fov: f32,
+        mapping: FisheyeMapping,
+        aspect: f32,
+    ) -> FisheyeCamera {
+        FisheyeCamera {
+            origin: lookfrom,
+            basis: Basis::new(lookfrom, lookat, vup),
+            fov: fov.to_radians(),
+            mapping,
+            aspect,
+            shutter: Shutter::default(),
+        }
+    }
+
+    /// the position on the image, where the image circle is the unit circle
+    fn image_pos(&self, s: f32, t: f32) -> (f32, f32) {
+        (
+            (2.0 * s - 1.0) * self.aspect.max(1.0),
+            (2.0 * t - 1.0) / self.aspect.min(1.0),
+        )
+    }
+}
+
+impl Camera for FisheyeCamera {
+    fn get_ray(&self, s: f32, t: f32) -> Ray {
+        let (x, y) = self.image_pos(s, t);
+        let r = (x * x + y * y).sqrt();
+        let theta = match self.mapping {
+            FisheyeMapping::Equidistant => r * self.fov / 2.0,
+            FisheyeMapping::Equisolid => 2.0 * (r * (self.fov / 4.0).sin()).min(1.0).asin(),
+        };
+        let Basis { u, v, w } = self.basis;
+        let side = if r > 0.0 {
+            (x / r) * u + (y / r) * v
+        } else {
+            Vec3::new(0.0, 0.0, 0.0)
+        };
+        Ray {
+            o: self.origin,
+            d: -theta.cos() * w + theta.sin() * side,
+            time: self.shutter.open,
+        }
+    }
+
+    fn in_view(&self, s: f32, t: f32) -> bool {
+        let (x, y) = self.image_pos(s, t);
+        x * x + y * y <= 1.0
+    }
+
+    fn name(&self) -> &'static str {
+        "fisheye"
+    }
+
+    fn eye(&self) -> Pt3 {
+        self.origin
+    }
+
+    fn view_dir(&self) -> Vec3 {
+        -self.basis.w
+    }
+
+    fn up(&self) -> Vec3 {
+        self.basis.v
+    }
+
+    fn shutter(&self) -> Shutter {
+        self.shutter
+    }
+
+    fn shutter_mut(&mut self) -> &mut Shutter {
+        &mut self.shutter
+    }
+}
+
+/// the six 90 degree faces of a cube, laid out in 3 columns and 2 rows
+/// ```text
+/// +---------+---------+---------+
+/// | right   | left    | up      |
+/// +---------+---------+---------+
+/// | down    | back    | front   |
+/// +---------+---------+---------+
+/// ```
+/// the order of +x, -x, +y, -y, +z, -z of a cube map in the camera space,
+/// so a 3:2 image gives square faces
+pub struct CubeMapCamera {
+    origin: Pt3,
+    basis: Basis,
+    pub shutter: Shutter,
+}
+
+impl CubeMapCamera {
+    pub fn new(lookfrom: Pt3, lookat: Pt3, vup: Vec3) -> CubeMapCamera {
+        CubeMapCamera {
+            origin: lookfrom,
+            basis: Basis::new(lookfrom, lookat, vup),
+            shutter: Shutter::default(),
+        }
+    }
+
+    /// the forward, right and up directions of the face
+    fn face(&self, k: usize) -> (Vec3, Vec3, Vec3) {
+        let Basis { u, v, w } = self.basis;
+        match k {
+            0 => (u, w, v),
+            1 => (-u, -w, v),
+            2 => (v, u, w),
+            3 => (-v, u, -w),
+            4 => (w, -u, v),
+            _ => (-w, u, v),
+        }
+    }
+}
+
+impl Camera for CubeMapCamera {
+    fn get_ray(&self, s: f32, t: f32) -> Ray {
+        let (x, y) = (s * 3.0, (1.0 - t) * 2.0);
+        let (col, row) = ((x as usize).min(2), (y as usize).min(1));
+        // the position on the face, from the bottom-left corner
+        let (a, b) = (x - col as f32, 1.0 - (y - row as f32));
+        let (forward, right, up) = self.face(row * 3 + col);
+        Ray {
+            o: self.origin,
+            d: forward + (2.0 * a - 1.0) * right + (2.0 * b - 1.0) * up,
+            time: self.shutter.open,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "cube map"
+    }
+
+    fn eye(&self) -> Pt3 {
+        self.origin
+    }
+
+    fn view_dir(&self) -> Vec3 {
+        -self.basis.w
+    }
+
+    fn up(&self) -> Vec3 {
+        self.basis.v
+    }
+
+    fn shutter(&self) -> Shutter {
+        self.shutter
+    }
+
+    fn shutter_mut(&mut self) -> &mut Shutter {
+        &mut self.shutter
+    }
+}
+
+/// the panoramic projections, which may replace the camera of a scene
+#[derive(Clone, Copy, Debug)]
+pub enum Projection {
+    Equirectangular,
+    /// a fisheye of 180 degrees
+    Fisheye(FisheyeMapping),
+    CubeMap,
+}
+
+impl Projection {
+    pub fn from_name(name: &str) -> Result<Projection, String> {
+        match name {
+            "equirect" => Ok(Projection::Equirectangular),
+            "fisheye" => Ok(Projection::Fisheye(FisheyeMapping::Equidistant)),
+            "fisheye-equisolid" => Ok(Projection::Fisheye(FisheyeMapping::Equisolid)),
+            "cubemap" => Ok(Projection::CubeMap),
+            _ => Err(format!("unknown projection `{}`", name)),
+        }
+    }
+
+    /// a camera of this projection, at the place of `cam`
+    pub fn camera(&self, cam: &dyn Camera, aspect: f32) -> Box<dyn Camera> {
+        let (lookfrom, lookat, vup) = (cam.eye(), cam.eye() + cam.view_dir(), cam.up());
+        let mut panorama: Box<dyn Camera> = match *self {
+            Projection::Equirectangular => {
+                Box::new(EquirectangularCamera::new(lookfrom, lookat, vup))
+            }
+            Projection::Fisheye(mapping) => Box::new(FisheyeCamera::new(
+                lookfrom, lookat, vup, 180.0, mapping, aspect,
+            )),
+            Projection::CubeMap => Box::new(CubeMapCamera::new(lookfrom, lookat, vup)),
+        };
+        *panorama.shutter_mut() = cam.shutter();
+        panorama
     }
 }
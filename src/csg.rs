@@ -0,0 +1,194 @@
+//! constructive solid geometry, the boolean operations on the solids
+//!
+//! the hits of both solids along a ray are merged in the order of `t`,
+//! the boundary of the result is where the ray gets in or out of it
+
+use crate::hitable::*;
+use crate::ray::Ray;
+use crate::*;
+use std::sync::Arc;
+
+#[derive(Clone, Copy, Debug)]
+pub enum CsgOp {
+    Union,
+    Intersection,
+    /// the first solid with the second one cut out of it
+    Difference,
+}
+
+impl CsgOp {
+    pub fn from_name(name: &str) -> Result<CsgOp, String> {
+        match name {
+            "union" => Ok(CsgOp::Union),
+            "intersection" => Ok(CsgOp::Intersection),
+            "difference" => Ok(CsgOp::Difference),
+            _ => Err(format!("unknown csg operation `{}`", name)),
+        }
+    }
+
+    /// whether a point is in the result, by whether it is in either solid
+    fn contains(&self, in_a: bool, in_b: bool) -> bool {
+        match self {
+            CsgOp::Union => in_a || in_b,
+            CsgOp::Intersection => in_a && in_b,
+            CsgOp::Difference => in_a && !in_b,
+        }
+    }
+}
+
+/// the combination of two solids, which is a solid too
+/// each part of the surface keeps the material of the solid it comes from
+pub struct Csg {
+    pub op: CsgOp,
+    pub a: Arc<dyn Solid>,
+    pub b: Arc<dyn Solid>,
+}
+
+/// the hit turned inside out, the cut of a difference faces the other way
+fn flipped(rec: &HitRecord) -> HitRecord {
+    HitRecord {
+        normal: -rec.normal,
+        geometric_normal: -rec.geometric_normal,
+        front_face: !rec.front_face,
+        // u runs backwards to keep dpdu x dpdv outwards
+        uv: Vec2::new(1.0 - rec.uv.x, rec.uv.y),
+        dpdu: -rec.dpdu,
+        ..rec.clone()
+    }
+}
+
+impl Solid for Csg {
+    fn hits(&self, r: &Ray, t_min: f32, t_max: f32) -> Vec<HitRecord> {
+        // all the hits beyond t_min, to tell whether the ray starts inside
+        let a = self.a.hits(r, t_min, f32::INFINITY);
+        let b = self.b.hits(r, t_min, f32::INFINITY);
+        let starts_inside = |hits: &[HitRecord]| hits.first().is_some_and(|h| !h.front_face);
+        let (mut in_a, mut in_b) = (starts_inside(&a), starts_inside(&b));
+        let mut inside = self.op.contains(in_a, in_b);
+        let mut hits = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        while i < a.len() || j < b.len() {
+            let rec = if j == b.len() || (i < a.len() && a[i].t <= b[j].t) {
+                in_a = a[i].front_face;
+                i += 1;
+                &a[i - 1]
+            } else {
+                in_b = b[j].front_face;
+                j += 1;
+                &b[j - 1]
+            };
+            if rec.t >= t_max {
+                break;
+            }
+            let now = self.op.contains(in_a, in_b);
+            if now != inside {
+                inside = now;
+                hits.push(if rec.front_face == now {
+                    rec.clone()
+                } else {
+                    flipped(rec)
+                });
+            }
+        }
+        hits
+    }
+}
+
+impl Hitable for Csg {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        self.hits(r, t_min, t_max).into_iter().next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::Sphere;
+    use crate::material::Diffuse;
+
+    /// the unit spheres at x = 0 and x = 1
+    fn csg(op: CsgOp) -> Csg {
+        let mat = Arc::new(Diffuse {
+            albedo: Vec3::new(0.5, 0.5, 0.5),
+        });
+        let sphere = |x| {
+            Arc::new(Sphere {
+                center: Vec3::new(x, 0.0, 0.0),
+                radius: 1.0,
+                mat: mat.clone(),
+            })
+        };
+        Csg {
+            op,
+            a: sphere(0.0),
+            b: sphere(1.0),
+        }
+    }
+
+    /// the parameters and the facing of the hits of the ray along +x from `x`
+    fn intervals(op: CsgOp, x: f32) -> Vec<(f32, bool)> {
+        let r = Ray {
+            o: Pt3::new(x, 0.0, 0.0),
+            d: Vec3::new(1.0, 0.0, 0.0),
+            time: 0.0,
+        };
+        csg(op)
+            .hits(&r, T_MIN, T_MAX)
+            .iter()
+            .map(|h| (h.t, h.front_face))
+            .collect()
+    }
+
+    fn assert_near(actual: &[(f32, bool)], expected: &[(f32, bool)]) {
+        assert_eq!(
+            actual.len(),
+            expected.len(),
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+        for (a, e) in actual.iter().zip(expected) {
+            assert!(
+                (a.0 - e.0).abs() < 1e-4 && a.1 == e.1,
+                "{:?} != {:?}",
+                actual,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn ray_from_outside() {
+        assert_near(&intervals(CsgOp::Union, -5.0), &[(4.0, true), (7.0, false)]);
+        assert_near(
+            &intervals(CsgOp::Intersection, -5.0),
+            &[(5.0, true), (6.0, false)],
+        );
+        assert_near(
+            &intervals(CsgOp::Difference, -5.0),
+            &[(4.0, true), (5.0, false)],
+        );
+    }
+
+    #[test]
+    fn ray_from_inside() {
+        // inside both spheres
+        assert_near(&intervals(CsgOp::Union, 0.5), &[(1.5, false)]);
+        assert_near(&intervals(CsgOp::Intersection, 0.5), &[(0.5, false)]);
+        assert_near(&intervals(CsgOp::Difference, 0.5), &[]);
+        // inside the first sphere only
+        assert_near(&intervals(CsgOp::Difference, -0.5), &[(0.5, false)]);
+    }
+
+    #[test]
+    fn difference_cut_faces_out() {
+        let r = Ray {
+            o: Pt3::new(-5.0, 0.0, 0.0),
+            d: Vec3::new(1.0, 0.0, 0.0),
+            time: 0.0,
+        };
+        let hits = csg(CsgOp::Difference).hits(&r, T_MIN, T_MAX);
+        // the cut by the second sphere faces the way the ray goes out
+        assert!((hits[1].normal - Vec3::new(1.0, 0.0, 0.0)).magnitude() < 1e-4);
+    }
+}
@@ -0,0 +1,158 @@
+use crate::hitable::*;
+use crate::image::Image;
+use crate::ray::*;
+use crate::shader::World;
+use crate::*;
+use cgmath::prelude::*;
+use std::ops::{AddAssign, Div};
+
+/// the geometry buffers of the first hit, which guide the denoiser
+#[derive(Clone, Copy)]
+pub struct Features {
+    pub albedo: Color,
+    pub normal: Vec3,
+    /// the distance from the camera
+    pub depth: f32,
+}
+
+impl Features {
+    pub fn zero() -> Self {
+        Features {
+            albedo: Color::new(0.0, 0.0, 0.0),
+            normal: Vec3::new(0.0, 0.0, 0.0),
+            depth: 0.0,
+        }
+    }
+
+    /// the features of the first hit of the camera ray, all zero if it hits nothing
+    pub fn first_hit(r: &Ray, world: &World) -> Self {
+        match world.objects.hit(r, T_MIN, T_MAX) {
+            Some(rec) => Features {
+                albedo: rec.mat.map_or(Color::new(0.0, 0.0, 0.0), |m| m.albedo()),
+                normal: rec.normal,
+                depth: rec.t * r.d.magnitude(),
+            },
+            None => Features::zero(),
+        }
+    }
+}
+
+impl AddAssign for Features {
+    fn add_assign(&mut self, other: Features) {
+        self.albedo += other.albedo;
+        self.normal += other.normal;
+        self.depth += other.depth;
+    }
+}
+
+impl Div<f32> for Features {
+    type Output = Features;
+    fn div(self, n: f32) -> Features {
+        Features {
+            albedo: self.albedo / n,
+            normal: self.normal / n,
+            depth: self.depth / n,
+        }
+    }
+}
+
+/// the parameters of the edge-avoiding à-trous wavelet filter
+pub struct Denoiser {
+    /// each iteration doubles the footprint of the 5x5 kernel
+    pub iterations: i32,
+    pub sigma_color: f32,
+    pub sigma_normal: f32,
+    /// of the depth relative to the distance
+    pub sigma_depth: f32,
+    pub sigma_albedo: f32,
+}
+
+impl Default for Denoiser {
+    fn default() -> Self {
+        Denoiser {
+            iterations: 5,
+            sigma_color: 0.5,
+            sigma_normal: 0.3,
+            sigma_depth: 0.1,
+            sigma_albedo: 0.3,
+        }
+    }
+}
+
+/// the B3 spline
+const KERNEL: [f32; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+/// the albedo below which the irradiance is not demodulated
+const DARK_ALBEDO: f32 = 1e-3;
+
+fn demodulate(c: Color, albedo: Color) -> Color {
+    let f = |c: f32, a: f32| if a > DARK_ALBEDO { c / a } else { c };
+    Color::new(f(c.x, albedo.x), f(c.y, albedo.y), f(c.z, albedo.z))
+}
+
+fn modulate(c: Color, albedo: Color) -> Color {
+    let f = |c: f32, a: f32| if a > DARK_ALBEDO { c * a } else { c };
+    Color::new(f(c.x, albedo.x), f(c.y, albedo.y), f(c.z, albedo.z))
+}
+
+// see: Dammertz, Holger, et al. Edge-avoiding à-trous wavelet transform for fast global illumination filtering
+impl Denoiser {
+    /// denoise the image, `features` in the same pixel order
+    /// the filter runs on the irradiance, the color divided by the albedo,
+    /// so the textures are kept sharp
+    pub fn denoise(&self, image: &Image, features: &[Features]) -> Image {
+        let (width, height) = (image.width, image.height);
+        let mut c: Vec<Color> = image
+            .pixels
+            .iter()
+            .zip(features.iter())
+            .map(|(c, f)| demodulate(*c, f.albedo))
+            .collect();
+
+        for it in 0..self.iterations {
+            let step = 1 << it;
+            // the color edges are trusted more as the noise is filtered out
+            let sigma_color = self.sigma_color / (1 << it) as f32;
+            let mut next = c.clone();
+            for y in 0..height {
+                for x in 0..width {
+                    let p = (y * width + x) as usize;
+                    let fp = &features[p];
+                    let mut sum = Color::new(0.0, 0.0, 0.0);
+                    let mut weight = 0.0;
+                    for (dy, ky) in KERNEL.iter().enumerate() {
+                        for (dx, kx) in KERNEL.iter().enumerate() {
+                            let qx = x + (dx as i32 - 2) * step;
+                            let qy = y + (dy as i32 - 2) * step;
+                            if qx < 0 || qx >= width || qy < 0 || qy >= height {
+                                continue;
+                            }
+                            let q = (qy * width + qx) as usize;
+                            let fq = &features[q];
+                            let w_c = -(c[p] - c[q]).magnitude2() / sigma_color.powi(2);
+                            let w_n =
+                                -(fp.normal - fq.normal).magnitude2() / self.sigma_normal.powi(2);
+                            let w_a =
+                                -(fp.albedo - fq.albedo).magnitude2() / self.sigma_albedo.powi(2);
+                            let w_d = -((fp.depth - fq.depth) / (fp.depth.max(1e-3) * step as f32))
+                                .powi(2)
+                                / self.sigma_depth.powi(2);
+                            let w = kx * ky * (w_c + w_n + w_a + w_d).exp();
+                            sum += w * c[q];
+                            weight += w;
+                        }
+                    }
+                    // the center pixel always has a positive weight
+                    next[p] = sum / weight;
+                }
+            }
+            c = next;
+        }
+
+        let mut out = Image::new(width, height);
+        for (p, (c, f)) in out.pixels.iter_mut().zip(c.iter().zip(features.iter())) {
+            *p = modulate(*c, f.albedo);
+        }
+        out
+    }
+}
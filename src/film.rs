@@ -0,0 +1,327 @@
+use crate::denoise::Features;
+use crate::filter::Filter;
+use crate::image::Image;
+use crate::tonemap::luminance;
+use crate::*;
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, BufWriter, Read, Write};
+
+/// the film accumulates the samples of all the rendering passes
+/// pixels are in row-major order from the top-left corner, the same as `Image`
+pub struct Film {
+    pub width: i32,
+    pub height: i32,
+    pub filter: Filter,
+    /// the sum of the radiance samples splatted into the pixel, weighted by the filter
+    sum: Vec<Color>,
+    /// the sum of the filter weights
+    weight: Vec<f32>,
+    /// the statistics of the samples taken in each pixel
+    stats: Vec<PixelStats>,
+    /// the sum of the first hit features of the samples taken in each pixel
+    features: Vec<Features>,
+}
+
+/// the luminance statistics of the samples taken in a pixel, for the variance
+#[derive(Clone, Copy, Default)]
+struct PixelStats {
+    lum: f32,
+    lum_sq: f32,
+    n: u32,
+}
+
+impl PixelStats {
+    fn merge(&mut self, other: &PixelStats) {
+        self.lum += other.lum;
+        self.lum_sq += other.lum_sq;
+        self.n += other.n;
+    }
+}
+
+/// the new samples of a pixel in a pass
+pub struct Samples {
+    /// the samples, with their offsets in the pixel, in the texture coordinate of the camera
+    points: Vec<(f32, f32, Color)>,
+    stats: PixelStats,
+    features: Features,
+}
+
+impl Samples {
+    pub fn new() -> Self {
+        Samples {
+            points: Vec::new(),
+            stats: PixelStats::default(),
+            features: Features::zero(),
+        }
+    }
+
+    /// add a sample at the offset (a, b) in the pixel
+    pub fn add(&mut self, a: f32, b: f32, col: Color, features: Features) {
+        self.points.push((a, b, col));
+        self.features += features;
+        let lum = luminance(col);
+        self.stats.lum += lum;
+        self.stats.lum_sq += lum * lum;
+        self.stats.n += 1;
+    }
+}
+
+impl Default for Samples {
+    fn default() -> Self {
+        Samples::new()
+    }
+}
+
+/// a part of the film rendered by a thread, with enough padding around the tile
+/// for the filter, merged into the film when finished
+pub struct FilmTile {
+    filter: Filter,
+    x0: i32,
+    y0: i32,
+    x1: i32,
+    y1: i32,
+    sum: Vec<Color>,
+    weight: Vec<f32>,
+    stats: Vec<(i32, i32, PixelStats, Features)>,
+}
+
+impl FilmTile {
+    fn index(&self, x: i32, y: i32) -> usize {
+        ((y - self.y0) * (self.x1 - self.x0) + x - self.x0) as usize
+    }
+
+    /// splat the samples of pixel (x, y) into the tile
+    pub fn add(&mut self, x: i32, y: i32, s: &Samples) {
+        for &(a, b, col) in s.points.iter() {
+            // the texture coordinate counts from the bottom, the film from the top
+            let (px, py) = (x as f32 + a, y as f32 + 1.0 - b);
+            let (fx0, fx1) = self.filter.footprint(px);
+            let (fy0, fy1) = self.filter.footprint(py);
+            for fy in fy0.max(self.y0)..=fy1.min(self.y1 - 1) {
+                for fx in fx0.max(self.x0)..=fx1.min(self.x1 - 1) {
+                    let w = self.filter.eval(fx as f32 + 0.5 - px, fy as f32 + 0.5 - py);
+                    let i = self.index(fx, fy);
+                    self.sum[i] += col * w;
+                    self.weight[i] += w;
+                }
+            }
+        }
+        self.stats.push((x, y, s.stats, s.features));
+    }
+}
+
+const CHECKPOINT_MAGIC: &[u8; 8] = b"RTCKPT04";
+
+/// the luminance below which the error is measured absolutely, instead of relatively,
+/// or the dark pixels would never converge
+const DARK_LUMINANCE: f32 = 0.01;
+
+fn read_u32<R: Read>(r: &mut R) -> io::Result<u32> {
+    let mut buf = [0u8; 4];
+    r.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_f32<R: Read>(r: &mut R) -> io::Result<f32> {
+    Ok(f32::from_bits(read_u32(r)?))
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+impl Film {
+    pub fn new(width: i32, height: i32, filter: Filter) -> Self {
+        let size = (width * height) as usize;
+        Film {
+            width,
+            height,
+            filter,
+            sum: vec![Color::new(0.0, 0.0, 0.0); size],
+            weight: vec![0.0; size],
+            stats: vec![PixelStats::default(); size],
+            features: vec![Features::zero(); size],
+        }
+    }
+
+    fn index(&self, x: i32, y: i32) -> usize {
+        (y * self.width + x) as usize
+    }
+
+    /// an empty tile for the pixels [x0, x1) * [y0, y1), padded by the filter radius
+    pub fn tile(&self, x0: i32, y0: i32, x1: i32, y1: i32) -> FilmTile {
+        let pad = self.filter.radius.ceil() as i32;
+        let (x0, y0) = ((x0 - pad).max(0), (y0 - pad).max(0));
+        let (x1, y1) = ((x1 + pad).min(self.width), (y1 + pad).min(self.height));
+        let size = ((x1 - x0) * (y1 - y0)) as usize;
+        FilmTile {
+            filter: self.filter,
+            x0,
+            y0,
+            x1,
+            y1,
+            sum: vec![Color::new(0.0, 0.0, 0.0); size],
+            weight: vec![0.0; size],
+            stats: Vec::new(),
+        }
+    }
+
+    /// add the finished tile to the film
+    pub fn merge(&mut self, tile: &FilmTile) {
+        for y in tile.y0..tile.y1 {
+            for x in tile.x0..tile.x1 {
+                let (i, k) = (self.index(x, y), tile.index(x, y));
+                self.sum[i] += tile.sum[k];
+                self.weight[i] += tile.weight[k];
+            }
+        }
+        for (x, y, stats, features) in tile.stats.iter() {
+            let i = self.index(*x, *y);
+            self.stats[i].merge(stats);
+            self.features[i] += *features;
+        }
+    }
+
+    /// the pixels [x0, x1) * [y0, y1) of the film
+    pub fn crop(&self, x0: i32, y0: i32, x1: i32, y1: i32) -> Film {
+        let mut film = Film::new(x1 - x0, y1 - y0, self.filter);
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let (i, k) = (self.index(x, y), film.index(x - x0, y - y0));
+                film.sum[k] = self.sum[i];
+                film.weight[k] = self.weight[i];
+                film.stats[k] = self.stats[i];
+                film.features[k] = self.features[i];
+            }
+        }
+        film
+    }
+
+    /// the estimated error of the pixel, the standard error of the mean luminance
+    /// relative to the mean, infinite if there are too few samples to tell
+    pub fn error(&self, x: i32, y: i32) -> f32 {
+        let s = self.stats[self.index(x, y)];
+        let n = s.n as f32;
+        if n < 2.0 {
+            return f32::INFINITY;
+        }
+        let mean = s.lum / n;
+        let var = ((s.lum_sq / n - mean * mean) * n / (n - 1.0)).max(0.0);
+        (var / n).sqrt() / mean.max(DARK_LUMINANCE)
+    }
+
+    /// the number of samples taken in the pixel
+    pub fn samples(&self, x: i32, y: i32) -> u32 {
+        self.stats[self.index(x, y)].n
+    }
+
+    /// the least number of samples among the pixels
+    pub fn min_samples(&self) -> u32 {
+        self.stats.iter().map(|s| s.n).min().unwrap_or(0)
+    }
+
+    /// the filtered radiance of each pixel
+    pub fn image(&self) -> Image {
+        let mut image = Image::new(self.width, self.height);
+        for (p, (sum, w)) in image
+            .pixels
+            .iter_mut()
+            .zip(self.sum.iter().zip(self.weight.iter()))
+        {
+            // the negative lobes may leave a pixel with no weight at all
+            if *w > 0.0 {
+                *p = sum / *w;
+            }
+        }
+        image
+    }
+
+    /// the mean first hit features of each pixel
+    pub fn features(&self) -> Vec<Features> {
+        self.features
+            .iter()
+            .zip(self.stats.iter())
+            .map(|(f, s)| if s.n > 0 { *f / s.n as f32 } else { *f })
+            .collect()
+    }
+
+    /// the number of samples of each pixel as a heatmap, from blue (none) to red (`max`)
+    pub fn heatmap(&self, max: u32) -> Image {
+        let mut image = Image::new(self.width, self.height);
+        for (p, s) in image.pixels.iter_mut().zip(self.stats.iter()) {
+            let t = (s.n as f32 / max as f32).min(1.0);
+            // blue -> cyan -> green -> yellow -> red
+            *p = Color::new(
+                (2.0 * t - 1.0).clamp(0.0, 1.0),
+                (2.0 - (4.0 * t - 2.0).abs()).clamp(0.0, 1.0),
+                (1.0 - 2.0 * t).clamp(0.0, 1.0),
+            );
+        }
+        image
+    }
+
+    /// save the film, with the hash of the settings it is rendered with
+    pub fn save_checkpoint(&self, path: &str, hash: u64) -> io::Result<()> {
+        // write to a temporary file first, so a crash never leaves a broken checkpoint
+        let tmp = format!("{}.tmp", path);
+        let mut w = BufWriter::new(File::create(&tmp)?);
+        w.write_all(CHECKPOINT_MAGIC)?;
+        w.write_all(&hash.to_le_bytes())?;
+        w.write_all(&(self.width as u32).to_le_bytes())?;
+        w.write_all(&(self.height as u32).to_le_bytes())?;
+        for i in 0..self.sum.len() {
+            let (sum, s, f) = (self.sum[i], self.stats[i], self.features[i]);
+            for c in [sum.x, sum.y, sum.z, self.weight[i], s.lum, s.lum_sq] {
+                w.write_all(&c.to_le_bytes())?;
+            }
+            w.write_all(&s.n.to_le_bytes())?;
+            for c in [f.albedo.x, f.albedo.y, f.albedo.z] {
+                w.write_all(&c.to_le_bytes())?;
+            }
+            for c in [f.normal.x, f.normal.y, f.normal.z, f.depth] {
+                w.write_all(&c.to_le_bytes())?;
+            }
+        }
+        w.flush()?;
+        drop(w);
+        std::fs::rename(&tmp, path)
+    }
+
+    /// load a film saved by `save_checkpoint`,
+    /// fails if it is rendered with other settings
+    pub fn load_checkpoint(path: &str, hash: u64, filter: Filter) -> io::Result<Film> {
+        let mut r = BufReader::new(File::open(path)?);
+        let mut magic = [0u8; 8];
+        r.read_exact(&mut magic)?;
+        if &magic != CHECKPOINT_MAGIC {
+            return Err(invalid_data(format!("{} is not a checkpoint", path)));
+        }
+        let mut buf = [0u8; 8];
+        r.read_exact(&mut buf)?;
+        if u64::from_le_bytes(buf) != hash {
+            return Err(invalid_data(format!(
+                "{} is rendered with different settings",
+                path
+            )));
+        }
+        let width = read_u32(&mut r)? as i32;
+        let height = read_u32(&mut r)? as i32;
+        let mut film = Film::new(width, height, filter);
+        for i in 0..(width * height) as usize {
+            film.sum[i] = Color::new(read_f32(&mut r)?, read_f32(&mut r)?, read_f32(&mut r)?);
+            film.weight[i] = read_f32(&mut r)?;
+            film.stats[i] = PixelStats {
+                lum: read_f32(&mut r)?,
+                lum_sq: read_f32(&mut r)?,
+                n: read_u32(&mut r)?,
+            };
+            film.features[i] = Features {
+                albedo: Color::new(read_f32(&mut r)?, read_f32(&mut r)?, read_f32(&mut r)?),
+                normal: Vec3::new(read_f32(&mut r)?, read_f32(&mut r)?, read_f32(&mut r)?),
+                depth: read_f32(&mut r)?,
+            };
+        }
+        Ok(film)
+    }
+}
@@ -0,0 +1,119 @@
+use std::f32::consts::PI;
+
+/// the pixel reconstruction filters
+#[derive(Clone, Copy, Debug)]
+pub enum FilterKind {
+    Box,
+    Tent,
+    Gaussian,
+    /// Mitchell-Netravali, with B = C = 1/3
+    Mitchell,
+    /// the windowed sinc, with as many lobes as the radius
+    Lanczos,
+}
+
+impl FilterKind {
+    pub fn from_name(name: &str) -> Result<FilterKind, String> {
+        match name {
+            "box" => Ok(FilterKind::Box),
+            "tent" => Ok(FilterKind::Tent),
+            "gaussian" => Ok(FilterKind::Gaussian),
+            "mitchell" => Ok(FilterKind::Mitchell),
+            "lanczos" => Ok(FilterKind::Lanczos),
+            _ => Err(format!("unknown filter `{}`", name)),
+        }
+    }
+
+    /// the radius usually used with the filter
+    pub fn default_radius(&self) -> f32 {
+        match self {
+            FilterKind::Box => 0.5,
+            FilterKind::Tent => 1.0,
+            FilterKind::Gaussian => 1.5,
+            FilterKind::Mitchell => 2.0,
+            FilterKind::Lanczos => 3.0,
+        }
+    }
+}
+
+/// a separable filter, a sample splats into all the pixels whose center is within `radius`
+#[derive(Clone, Copy, Debug)]
+pub struct Filter {
+    pub kind: FilterKind,
+    /// in pixels
+    pub radius: f32,
+}
+
+impl Default for Filter {
+    /// the box filter of a pixel, the plain average of the samples in it
+    fn default() -> Self {
+        Filter {
+            kind: FilterKind::Box,
+            radius: 0.5,
+        }
+    }
+}
+
+fn sinc(x: f32) -> f32 {
+    if x.abs() < 1e-5 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+// see: Mitchell, Don P. and Netravali, Arun N., Reconstruction Filters in Computer Graphics
+fn mitchell(x: f32) -> f32 {
+    let (b, c) = (1.0 / 3.0, 1.0 / 3.0);
+    let x = x.abs();
+    if x < 1.0 {
+        ((12.0 - 9.0 * b - 6.0 * c) * x.powi(3)
+            + (-18.0 + 12.0 * b + 6.0 * c) * x.powi(2)
+            + (6.0 - 2.0 * b))
+            / 6.0
+    } else if x < 2.0 {
+        ((-b - 6.0 * c) * x.powi(3)
+            + (6.0 * b + 30.0 * c) * x.powi(2)
+            + (-12.0 * b - 48.0 * c) * x
+            + (8.0 * b + 24.0 * c))
+            / 6.0
+    } else {
+        0.0
+    }
+}
+
+impl Filter {
+    /// the 1D filter at the distance `x` from the center
+    fn eval_1d(&self, x: f32) -> f32 {
+        let r = self.radius;
+        if x.abs() > r {
+            return 0.0;
+        }
+        match self.kind {
+            FilterKind::Box => 1.0,
+            FilterKind::Tent => 1.0 - x.abs() / r,
+            FilterKind::Gaussian => {
+                // the standard deviation of a third of the radius, shifted to 0 at the radius
+                let sigma2 = (r / 3.0).powi(2);
+                (-x * x / (2.0 * sigma2)).exp() - (-r * r / (2.0 * sigma2)).exp()
+            }
+            // the filter is defined on [-2, 2]
+            FilterKind::Mitchell => mitchell(x * 2.0 / r),
+            FilterKind::Lanczos => sinc(x) * sinc(x / r),
+        }
+    }
+
+    /// the weight of a sample at the offset (dx, dy) from the pixel center
+    pub fn eval(&self, dx: f32, dy: f32) -> f32 {
+        self.eval_1d(dx) * self.eval_1d(dy)
+    }
+
+    /// the pixels a sample at `p` (in continuous pixel coordinates) splats into, inclusive,
+    /// the pixel k has its center at k + 0.5
+    pub fn footprint(&self, p: f32) -> (i32, i32) {
+        (
+            (p - self.radius - 0.5).floor() as i32 + 1,
+            (p + self.radius - 0.5).floor() as i32,
+        )
+    }
+}
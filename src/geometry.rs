@@ -3,12 +3,14 @@ use crate::material::*;
 use crate::ray::Ray;
 use crate::*;
 use cgmath::prelude::*;
-use std::rc::Rc;
+use std::f32::consts::PI;
+use std::sync::Arc;
 
 // pub struct Triangle(pub Pt3, pub Pt3, pub Pt3);
+#[derive(Clone)]
 pub struct Triangle {
     pub vertex: (Pt3, Pt3, Pt3),
-    pub mat: Rc<dyn Material>,
+    pub mat: Arc<dyn Material>,
 }
 
 impl Triangle {
@@ -27,10 +29,10 @@ impl Triangle {
 
     /// the last parameter decides whether the triangle can be hit both side
     fn _hit(&self, r: &Ray, t_min: f32, t_max: f32, both_side: bool) -> Option<HitRecord> {
-        let _E1 = self.vertex.1 - self.vertex.0;
-        let _E2 = self.vertex.2 - self.vertex.0;
-        let _P = r.d.cross(_E2);
-        let det = _P.dot(_E1);
+        let e1 = self.vertex.1 - self.vertex.0;
+        let e2 = self.vertex.2 - self.vertex.0;
+        let pvec = r.d.cross(e2);
+        let det = pvec.dot(e1);
         // TODO
         if !both_side && det < 0.0 {
             return None;
@@ -39,26 +41,29 @@ impl Triangle {
             return None;
         }
         let inv_det = 1.0 / det;
-        let _T = r.o - self.vertex.0;
-        let _Q = _T.cross(_E1);
-        let u = inv_det * _P.dot(_T);
-        if u < 0.0 || u > 1.0 {
+        let tvec = r.o - self.vertex.0;
+        let qvec = tvec.cross(e1);
+        let u = inv_det * pvec.dot(tvec);
+        if !(0.0..=1.0).contains(&u) {
             return None;
         }
-        let v = inv_det * _Q.dot(r.d);
+        let v = inv_det * qvec.dot(r.d);
         if v < 0.0 || u + v > 1.0 {
             return None;
         }
-        let t = inv_det * _Q.dot(_E2);
+        let t = inv_det * qvec.dot(e2);
         if t - 0.0 < t_min || t > t_max {
             return None;
         }
+        // the surface parameters are the barycentric coordinates of the vertices 1 and 2
+        let rec = HitRecord::new(r, t, e1.cross(e2).normalize(), &self.mat).with_uv(
+            Vec2::new(u, v),
+            e1,
+            e2,
+        );
         Some(HitRecord {
-            t: t,
-            p: r.point_at_parameter(t), // TODO
-            normal: _E1.cross(_E2).normalize(),
-            // normal: self.normal(),
-            mat: Some(self.mat.clone()),
+            bary: Vec3::new(1.0 - u - v, u, v),
+            ..rec
         })
     }
 
@@ -76,118 +81,912 @@ impl Hitable for Triangle {
 pub struct Sphere {
     pub center: Vec3,
     pub radius: f32,
-    pub mat: Rc<dyn Material>,
+    pub mat: Arc<dyn Material>,
 }
 
-impl Hitable for Sphere {
-    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
-        let oc = r.o - self.center;
-        let a = r.d.dot(r.d);
-        let b = oc.dot(r.d);
-        let c = oc.dot(oc.to_vec()) - self.radius.powi(2);
-        let d = b.powi(2) - a * c;
-        if d > t_min {
-            let temp = (-b - (b * b - a * c).sqrt()) / a;
-            if temp < t_max && temp > t_min {
-                let t = temp;
-                let p = r.point_at_parameter(t);
-                let normal = (p - self.center) / self.radius;
-                return Some(HitRecord {
-                    t: t,
-                    p: p,
-                    normal: normal.to_vec(),
-                    mat: Some(self.mat.clone()),
-                });
-            }
-            let temp = (-b + (b * b - a * c).sqrt()) / a;
-            if temp < t_max && temp > t_min {
-                let t = temp;
+fn hit_sphere(
+    center: Vec3,
+    radius: f32,
+    mat: &Arc<dyn Material>,
+    r: &Ray,
+    t_min: f32,
+    t_max: f32,
+) -> Option<HitRecord> {
+    let oc = r.o - center;
+    let a = r.d.dot(r.d);
+    let b = oc.dot(r.d);
+    let c = oc.dot(oc.to_vec()) - radius.powi(2);
+    let d = b.powi(2) - a * c;
+    if d > t_min {
+        for t in [(-b - d.sqrt()) / a, (-b + d.sqrt()) / a] {
+            if t < t_max && t > t_min {
                 let p = r.point_at_parameter(t);
-                let normal = (p - self.center) / self.radius;
-                return Some(HitRecord {
-                    t: t,
-                    p: p,
-                    normal: normal.to_vec(),
-                    mat: Some(self.mat.clone()),
-                });
+                return Some(sphere_record(r, t, p - Pt3::from_vec(center), radius, mat));
             }
         }
-        None
     }
+    None
 }
 
-pub struct Cylinder {
-    pub center_x: f32,
-    pub center_z: f32,
-    pub y_min: f32,
-    pub y_max: f32,
+/// the hit at `offset` from the center of the sphere, parameterized by
+/// the longitude from +x to -z as u, and the latitude from the bottom to the top as v
+fn sphere_record(r: &Ray, t: f32, offset: Vec3, radius: f32, mat: &Arc<dyn Material>) -> HitRecord {
+    let n = offset / radius;
+    let phi = (-n.z).atan2(n.x).rem_euclid(2.0 * PI);
+    let theta = n.y.clamp(-1.0, 1.0).acos();
+    let (sin_phi, cos_phi) = phi.sin_cos();
+    let (sin_theta, cos_theta) = theta.sin_cos();
+    let dpdu = 2.0 * PI * Vec3::new(offset.z, 0.0, -offset.x);
+    let dpdv = -PI * radius * Vec3::new(cos_theta * cos_phi, -sin_theta, -cos_theta * sin_phi);
+    HitRecord::new(r, t, n, mat).with_uv(Vec2::new(phi / (2.0 * PI), 1.0 - theta / PI), dpdu, dpdv)
+}
+
+/// both hits of the sphere, in (t_min, t_max)
+fn sphere_hits(
+    center: Vec3,
+    radius: f32,
+    mat: &Arc<dyn Material>,
+    r: &Ray,
+    t_min: f32,
+    t_max: f32,
+) -> Vec<HitRecord> {
+    let oc = r.o - center;
+    let roots = solve_quadratic(
+        r.d.dot(r.d),
+        2.0 * oc.dot(r.d),
+        oc.dot(oc.to_vec()) - radius.powi(2),
+    );
+    let (t0, t1) = match roots {
+        Some(roots) => roots,
+        None => return Vec::new(),
+    };
+    [t0, t1]
+        .into_iter()
+        .filter(|&t| t > t_min && t < t_max)
+        .map(|t| {
+            let p = r.point_at_parameter(t);
+            sphere_record(r, t, p - Pt3::from_vec(center), radius, mat)
+        })
+        .collect()
+}
+
+impl Hitable for Sphere {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        hit_sphere(self.center, self.radius, &self.mat, r, t_min, t_max)
+    }
+}
+
+impl Solid for Sphere {
+    fn hits(&self, r: &Ray, t_min: f32, t_max: f32) -> Vec<HitRecord> {
+        sphere_hits(self.center, self.radius, &self.mat, r, t_min, t_max)
+    }
+}
+
+/// a sphere whose center moves linearly from `center0` at `time0` to `center1` at `time1`,
+/// and stays still out of the interval
+pub struct MovingSphere {
+    pub center0: Vec3,
+    pub center1: Vec3,
+    pub time0: f32,
+    pub time1: f32,
     pub radius: f32,
-    pub mat: Rc<dyn Material>,
+    pub mat: Arc<dyn Material>,
 }
 
-impl Hitable for Cylinder {
+impl MovingSphere {
+    pub fn center(&self, time: f32) -> Vec3 {
+        if self.time1 <= self.time0 {
+            return self.center0;
+        }
+        let k = ((time - self.time0) / (self.time1 - self.time0)).clamp(0.0, 1.0);
+        self.center0.lerp(self.center1, k)
+    }
+}
+
+impl Hitable for MovingSphere {
     fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
-        let t_top = (self.y_max - r.o.y) / r.d.y;
-        let t_bottom = (self.y_min - r.o.y) / r.d.y;
-        let p_top = r.point_at_parameter(t_top);
-        let d_top = Vec2::new(p_top.x - self.center_x, p_top.z - self.center_z);
-        let hit_top = t_top > t_min && t_top < t_max && d_top.dot(d_top) < self.radius.powi(2);
-        let p_bottom = r.point_at_parameter(t_bottom);
-        let d_bottom = Vec2::new(p_bottom.x - self.center_x, p_bottom.z - self.center_z);
-        let hit_bottom =
-            t_bottom > t_min && t_bottom < t_max && d_bottom.dot(d_bottom) < self.radius.powi(2);
-
-        let dxz = Vec2::new(r.d.x, r.d.z);
-        let oxz = Vec2::new(r.o.x - self.center_x, r.o.z - self.center_z);
-        let a = dxz.dot(dxz);
-        let b = dxz.dot(oxz);
-        let c = oxz.dot(oxz) - self.radius.powi(2);
-        let d = b.powi(2) - a * c;
-        if d > t_min {
-            let temp = (-b - d.sqrt()) / a;
-            if temp < t_max && temp > t_min {
-                let t = temp;
-                let p = r.point_at_parameter(t);
-                if p.y > self.y_min && p.y < self.y_max {
-                    return Some(HitRecord {
-                        t: t,
-                        p: p,
-                        normal: Vec3::new(p.x - self.center_x, 0.0, p.z - self.center_z)
-                            / self.radius,
-                        mat: Some(self.mat.clone()),
-                    });
+        hit_sphere(self.center(r.time), self.radius, &self.mat, r, t_min, t_max)
+    }
+}
+
+impl Solid for MovingSphere {
+    fn hits(&self, r: &Ray, t_min: f32, t_max: f32) -> Vec<HitRecord> {
+        sphere_hits(self.center(r.time), self.radius, &self.mat, r, t_min, t_max)
+    }
+}
+
+/// an orthonormal frame, the local space of the shapes around an axis,
+/// which is the y axis of the frame
+#[derive(Clone, Copy, Debug)]
+pub struct Frame {
+    pub origin: Pt3,
+    pub x: Vec3,
+    pub y: Vec3,
+    pub z: Vec3,
+}
+
+impl Frame {
+    /// the right-handed frame at `origin` with the y axis along `axis`,
+    /// it is the world space translated if `axis` is +y
+    pub fn new(origin: Pt3, axis: Vec3) -> Frame {
+        let y = axis.normalize();
+        let helper = if y.x.abs() < 0.9 {
+            Vec3::new(1.0, 0.0, 0.0)
+        } else {
+            Vec3::new(0.0, 0.0, 1.0)
+        };
+        let z = helper.cross(y).normalize();
+        Frame {
+            origin,
+            x: y.cross(z),
+            y,
+            z,
+        }
+    }
+
+    fn vec_to_local(&self, v: Vec3) -> Vec3 {
+        Vec3::new(v.dot(self.x), v.dot(self.y), v.dot(self.z))
+    }
+
+    fn vec_to_world(&self, v: Vec3) -> Vec3 {
+        v.x * self.x + v.y * self.y + v.z * self.z
+    }
+
+    /// the ray in the local space, with the same parameter `t` of the points
+    pub fn ray_to_local(&self, r: &Ray) -> Ray {
+        Ray {
+            o: Pt3::from_vec(self.vec_to_local(r.o - self.origin)),
+            d: self.vec_to_local(r.d),
+            time: r.time,
+        }
+    }
+
+    /// the hit of the ray in the local space, back to the world space
+    pub fn record_to_world(&self, rec: HitRecord) -> HitRecord {
+        HitRecord {
+            p: self.origin + self.vec_to_world(rec.p.to_vec()),
+            normal: self.vec_to_world(rec.normal),
+            geometric_normal: self.vec_to_world(rec.geometric_normal),
+            dpdu: self.vec_to_world(rec.dpdu),
+            dpdv: self.vec_to_world(rec.dpdv),
+            ..rec
+        }
+    }
+}
+
+/// the real roots of a t^2 + b t + c, in the ascending order,
+/// the single root is repeated if `a` is zero
+fn solve_quadratic(a: f32, b: f32, c: f32) -> Option<(f32, f32)> {
+    if a == 0.0 {
+        return if b == 0.0 {
+            None
+        } else {
+            Some((-c / b, -c / b))
+        };
+    }
+    let d = b * b - 4.0 * a * c;
+    if d < 0.0 {
+        return None;
+    }
+    // see: https://www.pbr-book.org/3ed-2018/Utilities/Mathematical_Routines#Quadratic
+    let q = -0.5 * (b + b.signum() * d.sqrt());
+    if q == 0.0 {
+        return Some((0.0, 0.0));
+    }
+    let (t0, t1) = (q / a, c / q);
+    Some((t0.min(t1), t0.max(t1)))
+}
+
+/// the largest real root of x^3 + a x^2 + b x + c
+fn largest_cubic_root(a: f64, b: f64, c: f64) -> f64 {
+    let q = (a * a - 3.0 * b) / 9.0;
+    let r = (2.0 * a * a * a - 9.0 * a * b + 27.0 * c) / 54.0;
+    let x = if r * r < q * q * q {
+        // three real roots
+        let theta = (r / (q * q * q).sqrt()).clamp(-1.0, 1.0).acos();
+        let k = -2.0 * q.sqrt();
+        (0..3)
+            .map(|i| k * ((theta + 2.0 * std::f64::consts::PI * i as f64) / 3.0).cos())
+            .fold(f64::MIN, f64::max)
+            - a / 3.0
+    } else {
+        let s = -r.signum() * (r.abs() + (r * r - q * q * q).sqrt()).cbrt();
+        let t = if s == 0.0 { 0.0 } else { q / s };
+        s + t - a / 3.0
+    };
+    // polish the root, the formulas above lose much precision
+    let f = |x: f64| ((x + a) * x + b) * x + c;
+    let df = |x: f64| (3.0 * x + 2.0 * a) * x + b;
+    (0..2).fold(x, |x, _| {
+        let d = df(x);
+        if d == 0.0 {
+            x
+        } else {
+            x - f(x) / d
+        }
+    })
+}
+
+/// the real roots of x^4 + a x^3 + b x^2 + c x + d, in no particular order
+// see: https://en.wikipedia.org/wiki/Quartic_function#Ferrari's_solution
+fn solve_quartic(a: f64, b: f64, c: f64, d: f64) -> Vec<f64> {
+    // the depressed quartic y^4 + p y^2 + q y + r, where x = y - a / 4
+    let p = b - 3.0 * a * a / 8.0;
+    let q = c - a * b / 2.0 + a * a * a / 8.0;
+    let r = d - a * c / 4.0 + a * a * b / 16.0 - 3.0 * a * a * a * a / 256.0;
+    let mut ys = Vec::with_capacity(4);
+    if q.abs() < 1e-12 {
+        // biquadratic, the roots of z^2 + p z + r, where z = y^2
+        let disc = p * p - 4.0 * r;
+        if disc >= 0.0 {
+            for z in [(-p - disc.sqrt()) / 2.0, (-p + disc.sqrt()) / 2.0] {
+                if z >= 0.0 {
+                    ys.push(z.sqrt());
+                    ys.push(-z.sqrt());
                 }
             }
-            if hit_top && t_top < t_bottom {
-                return Some(HitRecord {
-                    t: t_top,
-                    p: p_top,
-                    normal: Vec3::new(0.0, 1.0, 0.0),
-                    mat: Some(self.mat.clone()),
-                });
-            } else if hit_bottom {
-                return Some(HitRecord {
-                    t: t_bottom,
-                    p: p_bottom,
-                    normal: Vec3::new(0.0, -1.0, 0.0),
-                    mat: Some(self.mat.clone()),
-                });
+        }
+    } else {
+        // (y^2 + p / 2 + m)^2 = 2 m (y - q / 4m)^2, with a positive root m of the resolvent cubic
+        let m = largest_cubic_root(p, p * p / 4.0 - r, -q * q / 8.0);
+        if m > 0.0 {
+            let s = (2.0 * m).sqrt();
+            for (sign, k) in [(1.0, -1.0), (-1.0, 1.0)] {
+                // y^2 + sign * s y + (p / 2 + m + k * q / 2s)
+                let c0 = p / 2.0 + m + k * q / (2.0 * s);
+                let disc = s * s - 4.0 * c0;
+                if disc >= 0.0 {
+                    ys.push((-sign * s - disc.sqrt()) / 2.0);
+                    ys.push((-sign * s + disc.sqrt()) / 2.0);
+                }
             }
-            let temp = (-b + d.sqrt()) / a;
-            if temp < t_max && temp > t_min {
-                let t = temp;
-                let p = r.point_at_parameter(t);
-                if p.y > self.y_min && p.y < self.y_max {
-                    return Some(HitRecord {
-                        t: t,
-                        p: p,
-                        normal: Vec3::new(p.x - self.center_x, 0.0, p.z - self.center_z)
-                            / self.radius,
-                        mat: Some(self.mat.clone()),
-                    });
+        }
+    }
+    let f = |x: f64| (((x + a) * x + b) * x + c) * x + d;
+    let df = |x: f64| ((4.0 * x + 3.0 * a) * x + 2.0 * b) * x + c;
+    ys.into_iter()
+        .map(|y| {
+            (0..2).fold(y - a / 4.0, |x, _| {
+                let d = df(x);
+                if d == 0.0 {
+                    x
+                } else {
+                    x - f(x) / d
+                }
+            })
+        })
+        .collect()
+}
+
+/// the angle around the y axis from +x to -z, in [0, 2 pi)
+fn azimuth(p: Pt3) -> f32 {
+    (-p.z).atan2(p.x).rem_euclid(2.0 * PI)
+}
+
+/// the derivative of the point `p` over the azimuth normalized to [0, 1]
+fn azimuth_tangent(p: Pt3) -> Vec3 {
+    2.0 * PI * Vec3::new(p.z, 0.0, -p.x)
+}
+
+/// the hit on the cap of the radius at the height `y` in the local space, facing up (`side` 1)
+/// or down (`side` -1), parameterized by the square around it, as seen from the outside
+fn hit_cap(
+    r: &Ray,
+    y: f32,
+    radius: f32,
+    side: f32,
+    mat: &Arc<dyn Material>,
+    t_min: f32,
+    t_max: f32,
+) -> Option<HitRecord> {
+    if r.d.y == 0.0 || radius <= 0.0 {
+        return None;
+    }
+    let t = (y - r.o.y) / r.d.y;
+    if t <= t_min || t >= t_max {
+        return None;
+    }
+    let p = r.point_at_parameter(t);
+    if p.x * p.x + p.z * p.z > radius * radius {
+        return None;
+    }
+    let d = 2.0 * radius;
+    Some(
+        HitRecord::new(r, t, Vec3::new(0.0, side, 0.0), mat).with_uv(
+            Vec2::new(0.5 + p.x / d, 0.5 - side * p.z / d),
+            Vec3::new(d, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -side * d),
+        ),
+    )
+}
+
+/// the shapes around the y axis of their frames, from the height 0 up to `height`,
+/// with the side of a quadratic surface and the ends optionally closed by disks
+trait AxialShape {
+    fn frame(&self) -> &Frame;
+    fn height(&self) -> f32;
+    fn mat(&self) -> &Arc<dyn Material>;
+    /// the coefficients of the equation `a t^2 + b t + c = 0` of the side, in the local space
+    fn side_equation(&self, r: &Ray) -> (f32, f32, f32);
+    /// the hit on the side at `p`, in the local space
+    fn side_record(&self, r: &Ray, t: f32, p: Pt3) -> HitRecord;
+    /// the heights, radii and facing of the disks closing the ends, zero radius for an open end
+    fn caps(&self) -> [(f32, f32, f32); 2];
+}
+
+/// the hits of the shape in the local space, unsorted, or the closest one if `closest`
+fn axial_hits<S: AxialShape>(
+    s: &S,
+    r: &Ray,
+    t_min: f32,
+    mut t_max: f32,
+    closest: bool,
+) -> Vec<HitRecord> {
+    let mut hits = Vec::new();
+    let (a, b, c) = s.side_equation(r);
+    if let Some((t0, t1)) = solve_quadratic(a, b, c) {
+        let roots = if t0 == t1 { &[t0][..] } else { &[t0, t1][..] };
+        for &t in roots {
+            if t <= t_min || t >= t_max {
+                continue;
+            }
+            let p = r.point_at_parameter(t);
+            if (0.0..=s.height()).contains(&p.y) {
+                hits.push(s.side_record(r, t, p));
+                if closest {
+                    t_max = t;
+                    break;
                 }
             }
         }
-        None
+    }
+    for (y, radius, facing) in s.caps() {
+        if let Some(rec) = hit_cap(r, y, radius, facing, s.mat(), t_min, t_max) {
+            if closest {
+                t_max = rec.t;
+                hits.clear();
+            }
+            hits.push(rec);
+        }
+    }
+    hits
+}
+
+fn axial_hit<S: AxialShape>(s: &S, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+    let r = s.frame().ray_to_local(r);
+    let rec = axial_hits(s, &r, t_min, t_max, true).pop()?;
+    Some(s.frame().record_to_world(rec))
+}
+
+fn axial_solid_hits<S: AxialShape>(s: &S, r: &Ray, t_min: f32, t_max: f32) -> Vec<HitRecord> {
+    let r = s.frame().ray_to_local(r);
+    let mut hits = axial_hits(s, &r, t_min, t_max, false);
+    sort_hits(&mut hits);
+    hits.into_iter()
+        .map(|rec| s.frame().record_to_world(rec))
+        .collect()
+}
+
+/// a cylinder from the center of the base to the center of the top,
+/// parameterized by the angle around the axis as u, and the height from the base as v
+pub struct Cylinder {
+    pub frame: Frame,
+    pub height: f32,
+    pub radius: f32,
+    /// whether the ends are closed by disks, only a closed cylinder is a solid
+    pub caps: bool,
+    pub mat: Arc<dyn Material>,
+}
+
+impl Cylinder {
+    /// `base` and `top` should differ
+    pub fn new(base: Pt3, top: Pt3, radius: f32, caps: bool, mat: Arc<dyn Material>) -> Self {
+        Cylinder {
+            frame: Frame::new(base, top - base),
+            height: (top - base).magnitude(),
+            radius,
+            caps,
+            mat,
+        }
+    }
+}
+
+impl AxialShape for Cylinder {
+    fn frame(&self) -> &Frame {
+        &self.frame
+    }
+
+    fn height(&self) -> f32 {
+        self.height
+    }
+
+    fn mat(&self) -> &Arc<dyn Material> {
+        &self.mat
+    }
+
+    fn side_equation(&self, r: &Ray) -> (f32, f32, f32) {
+        (
+            r.d.x * r.d.x + r.d.z * r.d.z,
+            2.0 * (r.o.x * r.d.x + r.o.z * r.d.z),
+            r.o.x * r.o.x + r.o.z * r.o.z - self.radius * self.radius,
+        )
+    }
+
+    fn side_record(&self, r: &Ray, t: f32, p: Pt3) -> HitRecord {
+        HitRecord::new(r, t, Vec3::new(p.x, 0.0, p.z) / self.radius, &self.mat).with_uv(
+            Vec2::new(azimuth(p) / (2.0 * PI), p.y / self.height),
+            azimuth_tangent(p),
+            Vec3::new(0.0, self.height, 0.0),
+        )
+    }
+
+    fn caps(&self) -> [(f32, f32, f32); 2] {
+        let radius = if self.caps { self.radius } else { 0.0 };
+        [(self.height, radius, 1.0), (0.0, radius, -1.0)]
+    }
+}
+
+impl Hitable for Cylinder {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        axial_hit(self, r, t_min, t_max)
+    }
+}
+
+impl Solid for Cylinder {
+    fn hits(&self, r: &Ray, t_min: f32, t_max: f32) -> Vec<HitRecord> {
+        axial_solid_hits(self, r, t_min, t_max)
+    }
+}
+
+/// a cone, or a frustum of it, from the center of the base to the center of the top,
+/// parameterized by the angle around the axis as u, and the height from the base as v
+pub struct Cone {
+    pub frame: Frame,
+    pub height: f32,
+    pub base_radius: f32,
+    /// zero for a cone, whose apex is at the top
+    pub top_radius: f32,
+    /// whether the ends of nonzero radii are closed by disks, only a closed cone is a solid
+    pub caps: bool,
+    pub mat: Arc<dyn Material>,
+}
+
+impl Cone {
+    /// `base` and `top` should differ, and either of the radii should be positive
+    pub fn new(
+        base: Pt3,
+        top: Pt3,
+        base_radius: f32,
+        top_radius: f32,
+        caps: bool,
+        mat: Arc<dyn Material>,
+    ) -> Self {
+        Cone {
+            frame: Frame::new(base, top - base),
+            height: (top - base).magnitude(),
+            base_radius,
+            top_radius,
+            caps,
+            mat,
+        }
+    }
+
+    /// the slope of the radius over the height
+    fn slope(&self) -> f32 {
+        (self.top_radius - self.base_radius) / self.height
+    }
+}
+
+impl AxialShape for Cone {
+    fn frame(&self) -> &Frame {
+        &self.frame
+    }
+
+    fn height(&self) -> f32 {
+        self.height
+    }
+
+    fn mat(&self) -> &Arc<dyn Material> {
+        &self.mat
+    }
+
+    fn side_equation(&self, r: &Ray) -> (f32, f32, f32) {
+        // x^2 + z^2 = (r0 + k y)^2
+        let k = self.slope();
+        let ro = self.base_radius + k * r.o.y;
+        (
+            r.d.x * r.d.x + r.d.z * r.d.z - k * k * r.d.y * r.d.y,
+            2.0 * (r.o.x * r.d.x + r.o.z * r.d.z - k * ro * r.d.y),
+            r.o.x * r.o.x + r.o.z * r.o.z - ro * ro,
+        )
+    }
+
+    fn side_record(&self, r: &Ray, t: f32, p: Pt3) -> HitRecord {
+        let k = self.slope();
+        let rho = self.base_radius + k * p.y;
+        let normal = Vec3::new(p.x, -k * rho, p.z).normalize();
+        // the direction from the axis, which is arbitrary at the apex
+        let radial = if rho > 0.0 {
+            Vec3::new(p.x, 0.0, p.z) / rho
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let dpdv = self.height * (k * radial + Vec3::new(0.0, 1.0, 0.0));
+        HitRecord::new(r, t, normal, &self.mat).with_uv(
+            Vec2::new(azimuth(p) / (2.0 * PI), p.y / self.height),
+            azimuth_tangent(p),
+            dpdv,
+        )
+    }
+
+    fn caps(&self) -> [(f32, f32, f32); 2] {
+        if self.caps {
+            [
+                (self.height, self.top_radius, 1.0),
+                (0.0, self.base_radius, -1.0),
+            ]
+        } else {
+            [(0.0, 0.0, 1.0); 2]
+        }
+    }
+}
+
+impl Hitable for Cone {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        axial_hit(self, r, t_min, t_max)
+    }
+}
+
+impl Solid for Cone {
+    fn hits(&self, r: &Ray, t_min: f32, t_max: f32) -> Vec<HitRecord> {
+        axial_solid_hits(self, r, t_min, t_max)
+    }
+}
+
+/// a disk, or an annulus with a hole in the middle, which can be hit from both sides,
+/// parameterized by the angle around the normal as u, and the distance from the rim as v
+pub struct Disk {
+    pub frame: Frame,
+    pub radius: f32,
+    pub inner_radius: f32,
+    pub mat: Arc<dyn Material>,
+}
+
+impl Disk {
+    pub fn new(
+        center: Pt3,
+        normal: Vec3,
+        radius: f32,
+        inner_radius: f32,
+        mat: Arc<dyn Material>,
+    ) -> Self {
+        Disk {
+            frame: Frame::new(center, normal),
+            radius,
+            inner_radius,
+            mat,
+        }
+    }
+}
+
+impl Hitable for Disk {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let r = self.frame.ray_to_local(r);
+        if r.d.y == 0.0 {
+            return None;
+        }
+        let t = -r.o.y / r.d.y;
+        if t <= t_min || t >= t_max {
+            return None;
+        }
+        let p = r.point_at_parameter(t);
+        let rho = (p.x * p.x + p.z * p.z).sqrt();
+        if rho > self.radius || rho < self.inner_radius || rho == 0.0 {
+            return None;
+        }
+        let width = self.radius - self.inner_radius;
+        let rec = HitRecord::new(&r, t, Vec3::new(0.0, 1.0, 0.0), &self.mat).with_uv(
+            Vec2::new(azimuth(p) / (2.0 * PI), (self.radius - rho) / width),
+            azimuth_tangent(p),
+            -width / rho * Vec3::new(p.x, 0.0, p.z),
+        );
+        Some(self.frame.record_to_world(rec))
+    }
+}
+
+/// a torus around the axis through its center, parameterized by the angle around the axis as u,
+/// and the angle around the tube from the outer equator towards the axis as v
+pub struct Torus {
+    pub frame: Frame,
+    /// the distance from the center to the middle of the tube
+    pub major_radius: f32,
+    /// the radius of the tube
+    pub minor_radius: f32,
+    pub mat: Arc<dyn Material>,
+}
+
+impl Torus {
+    pub fn new(
+        center: Pt3,
+        axis: Vec3,
+        major_radius: f32,
+        minor_radius: f32,
+        mat: Arc<dyn Material>,
+    ) -> Self {
+        Torus {
+            frame: Frame::new(center, axis),
+            major_radius,
+            minor_radius,
+            mat,
+        }
+    }
+
+    /// the parameters of the hits of the ray in the local space, in (t_min, t_max), unsorted
+    fn roots(&self, r: &Ray, t_min: f32, t_max: f32) -> Vec<f32> {
+        let (big_r, small_r) = (self.major_radius as f64, self.minor_radius as f64);
+        let len = r.d.magnitude() as f64;
+        let (d, o) = match (r.d.cast::<f64>(), r.o.to_vec().cast::<f64>()) {
+            (Some(d), Some(o)) => (d / len, o),
+            _ => return Vec::new(),
+        };
+        // start from the bounding sphere, the coefficients are much smaller near the torus
+        let b = o.dot(d);
+        let bound = b * b - o.dot(o) + (big_r + small_r).powi(2);
+        if bound < 0.0 {
+            return Vec::new();
+        }
+        let start = (-b - bound.sqrt()).max(0.0);
+        let o = o + start * d;
+        // (|p|^2 + R^2 - r^2)^2 = 4 R^2 (x^2 + z^2), where p = o + s d and |d| = 1
+        let (p, q) = (o.dot(d), o.dot(o) + big_r * big_r - small_r * small_r);
+        let k = 4.0 * big_r * big_r;
+        let roots = solve_quartic(
+            4.0 * p,
+            4.0 * p * p + 2.0 * q - k * (d.x * d.x + d.z * d.z),
+            4.0 * p * q - 2.0 * k * (o.x * d.x + o.z * d.z),
+            q * q - k * (o.x * o.x + o.z * o.z),
+        );
+        roots
+            .into_iter()
+            .map(|s| ((start + s) / len) as f32)
+            .filter(|&t| t > t_min && t < t_max)
+            .collect()
+    }
+
+    /// the hit of the ray in the local space
+    fn record(&self, r: &Ray, t: f32) -> HitRecord {
+        let p = r.point_at_parameter(t);
+        let radial = Vec3::new(p.x, 0.0, p.z).normalize();
+        let offset = p.to_vec() - self.major_radius * radial;
+        let normal = offset.normalize();
+        let theta = offset.y.atan2(offset.dot(radial)).rem_euclid(2.0 * PI);
+        let (sin_theta, cos_theta) = theta.sin_cos();
+        let dpdv = 2.0
+            * PI
+            * self.minor_radius
+            * (-sin_theta * radial + cos_theta * Vec3::new(0.0, 1.0, 0.0));
+        HitRecord::new(r, t, normal, &self.mat).with_uv(
+            Vec2::new(azimuth(p) / (2.0 * PI), theta / (2.0 * PI)),
+            azimuth_tangent(p),
+            dpdv,
+        )
+    }
+}
+
+impl Hitable for Torus {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let r = self.frame.ray_to_local(r);
+        let t = self.roots(&r, t_min, t_max).into_iter().reduce(f32::min)?;
+        Some(self.frame.record_to_world(self.record(&r, t)))
+    }
+}
+
+impl Solid for Torus {
+    fn hits(&self, r: &Ray, t_min: f32, t_max: f32) -> Vec<HitRecord> {
+        let r = self.frame.ray_to_local(r);
+        let mut roots = self.roots(&r, t_min, t_max);
+        roots.sort_by(f32::total_cmp);
+        roots
+            .into_iter()
+            .map(|t| self.frame.record_to_world(self.record(&r, t)))
+            .collect()
+    }
+}
+
+/// a paraboloid from the vertex up to the rim of the radius at the top,
+/// parameterized by the angle around the axis as u, and the height from the vertex as v
+pub struct Paraboloid {
+    pub frame: Frame,
+    pub height: f32,
+    pub radius: f32,
+    /// whether the top is closed by a disk, only a closed paraboloid is a solid
+    pub cap: bool,
+    pub mat: Arc<dyn Material>,
+}
+
+impl Paraboloid {
+    /// `vertex` and `top` should differ
+    pub fn new(vertex: Pt3, top: Pt3, radius: f32, cap: bool, mat: Arc<dyn Material>) -> Self {
+        Paraboloid {
+            frame: Frame::new(vertex, top - vertex),
+            height: (top - vertex).magnitude(),
+            radius,
+            cap,
+            mat,
+        }
+    }
+}
+
+impl AxialShape for Paraboloid {
+    fn frame(&self) -> &Frame {
+        &self.frame
+    }
+
+    fn height(&self) -> f32 {
+        self.height
+    }
+
+    fn mat(&self) -> &Arc<dyn Material> {
+        &self.mat
+    }
+
+    fn side_equation(&self, r: &Ray) -> (f32, f32, f32) {
+        // x^2 + z^2 = k y
+        let k = self.radius * self.radius / self.height;
+        (
+            r.d.x * r.d.x + r.d.z * r.d.z,
+            2.0 * (r.o.x * r.d.x + r.o.z * r.d.z) - k * r.d.y,
+            r.o.x * r.o.x + r.o.z * r.o.z - k * r.o.y,
+        )
+    }
+
+    fn side_record(&self, r: &Ray, t: f32, p: Pt3) -> HitRecord {
+        let k = self.radius * self.radius / self.height;
+        let normal = Vec3::new(2.0 * p.x, -k, 2.0 * p.z).normalize();
+        // the slope of the radius over the height is k / 2 rho, infinite at the vertex
+        let rho2 = (p.x * p.x + p.z * p.z).max(1e-12);
+        let dpdv =
+            self.height * (k / (2.0 * rho2) * Vec3::new(p.x, 0.0, p.z) + Vec3::new(0.0, 1.0, 0.0));
+        HitRecord::new(r, t, normal, &self.mat).with_uv(
+            Vec2::new(azimuth(p) / (2.0 * PI), p.y / self.height),
+            azimuth_tangent(p),
+            dpdv,
+        )
+    }
+
+    fn caps(&self) -> [(f32, f32, f32); 2] {
+        let radius = if self.cap { self.radius } else { 0.0 };
+        [(self.height, radius, 1.0), (0.0, 0.0, -1.0)]
+    }
+}
+
+impl Hitable for Paraboloid {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        axial_hit(self, r, t_min, t_max)
+    }
+}
+
+impl Solid for Paraboloid {
+    fn hits(&self, r: &Ray, t_min: f32, t_max: f32) -> Vec<HitRecord> {
+        axial_solid_hits(self, r, t_min, t_max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn torus() -> Torus {
+        let mat = Arc::new(Diffuse {
+            albedo: Vec3::new(0.5, 0.5, 0.5),
+        });
+        Torus::new(
+            Pt3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            2.0,
+            0.5,
+            mat,
+        )
+    }
+
+    fn ray(o: Pt3, d: Vec3) -> Ray {
+        Ray { o, d, time: 0.0 }
+    }
+
+    fn ts(hits: &[HitRecord]) -> Vec<f32> {
+        hits.iter().map(|h| h.t).collect()
+    }
+
+    fn assert_near(actual: &[f32], expected: &[f32], eps: f32) {
+        assert_eq!(
+            actual.len(),
+            expected.len(),
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < eps, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn quartic_distinct_roots() {
+        // (x - 1)(x - 2)(x - 3)(x - 4)
+        let mut roots = solve_quartic(-10.0, 35.0, -50.0, 24.0);
+        roots.sort_by(f64::total_cmp);
+        assert_eq!(roots.len(), 4);
+        for (x, e) in roots.iter().zip([1.0, 2.0, 3.0, 4.0]) {
+            assert!((x - e).abs() < 1e-9, "{:?}", roots);
+        }
+    }
+
+    #[test]
+    fn torus_ray_across_axis() {
+        let torus = torus();
+        // through both sides of the tube, entering and leaving each
+        let hits = torus.hits(
+            &ray(Pt3::new(-5.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0)),
+            T_MIN,
+            T_MAX,
+        );
+        assert_near(&ts(&hits), &[2.5, 3.5, 6.5, 7.5], 1e-4);
+        let faces: Vec<bool> = hits.iter().map(|h| h.front_face).collect();
+        assert_eq!(faces, [true, false, true, false]);
+        // the parameters scale with the direction
+        let hits = torus.hits(
+            &ray(Pt3::new(-5.0, 0.0, 0.0), Vec3::new(2.0, 0.0, 0.0)),
+            T_MIN,
+            T_MAX,
+        );
+        assert_near(&ts(&hits), &[1.25, 1.75, 3.25, 3.75], 1e-4);
+    }
+
+    #[test]
+    fn torus_ray_along_axis() {
+        // down the hole in the middle
+        let r = ray(Pt3::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!(torus().hits(&r, T_MIN, T_MAX).is_empty());
+        assert!(torus().hit(&r, T_MIN, T_MAX).is_none());
+    }
+
+    #[test]
+    fn torus_grazing_ray() {
+        let torus = torus();
+        // just below the top of the tube, the roots come in close pairs
+        let hits = torus.hits(
+            &ray(Pt3::new(-5.0, 0.49, 0.0), Vec3::new(1.0, 0.0, 0.0)),
+            T_MIN,
+            T_MAX,
+        );
+        let half = (0.5f32 * 0.5 - 0.49 * 0.49).sqrt();
+        assert_near(
+            &ts(&hits),
+            &[3.0 - half, 3.0 + half, 7.0 - half, 7.0 + half],
+            1e-3,
+        );
+        // tangent to the top of the tube, any hits are at the tangent points
+        let hits = torus.hits(
+            &ray(Pt3::new(-5.0, 0.5, 0.0), Vec3::new(1.0, 0.0, 0.0)),
+            T_MIN,
+            T_MAX,
+        );
+        for t in ts(&hits) {
+            assert!((t - 3.0).abs() < 1e-2 || (t - 7.0).abs() < 1e-2, "{}", t);
+        }
+    }
+
+    #[test]
+    fn torus_missing_ray() {
+        let torus = torus();
+        // through the bounding sphere, above the tube
+        let r = ray(Pt3::new(-5.0, 1.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!(torus.hits(&r, T_MIN, T_MAX).is_empty());
+        // out of the bounding sphere
+        let r = ray(Pt3::new(-5.0, 3.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!(torus.hits(&r, T_MIN, T_MAX).is_empty());
+        // away from the torus
+        let r = ray(Pt3::new(-5.0, 0.0, 0.0), Vec3::new(-1.0, 0.0, 0.0));
+        assert!(torus.hit(&r, T_MIN, T_MAX).is_none());
     }
 }
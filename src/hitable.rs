@@ -1,27 +1,86 @@
 use crate::material::*;
 use crate::ray::Ray;
 use crate::*;
-use std::rc::Rc;
+use cgmath::prelude::*;
+use cgmath::Matrix4;
+use std::sync::Arc;
 
 #[derive(Clone)]
 pub struct HitRecord {
     pub t: f32,
     pub p: Pt3,
+    /// the shading normal, pointing outwards
     pub normal: Vec3,
-    pub mat: Option<Rc<dyn Material>>,
+    /// the normal of the actual surface, pointing outwards
+    pub geometric_normal: Vec3,
+    /// whether the ray hits the outside of the surface, against the geometric normal
+    pub front_face: bool,
+    /// the surface parameters
+    pub uv: Vec2,
+    /// the partial derivatives of the point over the surface parameters,
+    /// `dpdu` x `dpdv` points outwards
+    pub dpdu: Vec3,
+    pub dpdv: Vec3,
+    /// the barycentric coordinates of the point in a triangle, zero for the other shapes
+    pub bary: Vec3,
+    pub mat: Option<Arc<dyn Material>>,
 }
 
 pub const EMPTY_REC: HitRecord = HitRecord {
     t: 0.0,
     p: Pt3::new(0.0, 0.0, 0.0),
     normal: Vec3::new(0.0, 0.0, 0.0),
+    geometric_normal: Vec3::new(0.0, 0.0, 0.0),
+    front_face: true,
+    uv: Vec2::new(0.0, 0.0),
+    dpdu: Vec3::new(0.0, 0.0, 0.0),
+    dpdv: Vec3::new(0.0, 0.0, 0.0),
+    bary: Vec3::new(0.0, 0.0, 0.0),
     mat: None,
 };
 
-pub trait Hitable {
+impl HitRecord {
+    /// the hit of `r` at `t` on the surface of the outward unit `normal`,
+    /// which is the shading normal too, with no surface parameters
+    pub fn new(r: &Ray, t: f32, normal: Vec3, mat: &Arc<dyn Material>) -> HitRecord {
+        HitRecord {
+            t,
+            p: r.point_at_parameter(t),
+            normal,
+            geometric_normal: normal,
+            front_face: r.d.dot(normal) < 0.0,
+            mat: Some(mat.clone()),
+            ..EMPTY_REC
+        }
+    }
+
+    /// with the surface parameters and their partial derivatives
+    pub fn with_uv(self, uv: Vec2, dpdu: Vec3, dpdv: Vec3) -> HitRecord {
+        HitRecord {
+            uv,
+            dpdu,
+            dpdv,
+            ..self
+        }
+    }
+}
+
+/// hitables are shared by all the rendering threads
+pub trait Hitable: Send + Sync {
     fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord>;
 }
 
+/// the closed shapes, whose inside is well defined
+pub trait Solid: Hitable {
+    /// all the hits of the ray in (t_min, t_max), in the ascending order of `t`,
+    /// `front_face` tells whether the ray enters the solid or leaves it there
+    fn hits(&self, r: &Ray, t_min: f32, t_max: f32) -> Vec<HitRecord>;
+}
+
+pub fn sort_hits(hits: &mut [HitRecord]) {
+    hits.sort_by(|a, b| a.t.total_cmp(&b.t));
+}
+
 pub struct HitableList {
     pub list: Vec<Box<dyn Hitable>>,
 }
@@ -32,12 +91,7 @@ impl Hitable for Box<dyn Hitable> {
     }
 }
 
-pub fn hit_list<T: Hitable>(
-    list: &Vec<T>,
-    r: &Ray,
-    t_min: f32,
-    t_max: f32,
-) -> Option<HitRecord> {
+pub fn hit_list<T: Hitable>(list: &[T], r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
     let mut hit_anything = false;
     let mut closest_so_far = t_max;
     let mut rec = EMPTY_REC;
@@ -60,3 +114,147 @@ impl Hitable for HitableList {
         hit_list(&self.list, r, t_min, t_max)
     }
 }
+
+/// a shared hitable placed in the world by an affine transform,
+/// the same object can be placed many times without copying it
+pub struct Instance {
+    pub object: Arc<dyn Hitable>,
+    /// from the space of the object to the world
+    pub transform: Matrix4<f32>,
+    pub inverse: Matrix4<f32>,
+}
+
+impl Instance {
+    /// none if the transform is not invertible
+    pub fn new(object: Arc<dyn Hitable>, transform: Matrix4<f32>) -> Option<Self> {
+        Some(Instance {
+            object,
+            transform,
+            inverse: transform.invert()?,
+        })
+    }
+}
+
+impl Hitable for Instance {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        // the parameter of the points stays the same under an affine transform
+        let local = Ray {
+            o: self.inverse.transform_point(r.o),
+            d: self.inverse.transform_vector(r.d),
+            time: r.time,
+        };
+        let rec = self.object.hit(&local, t_min, t_max)?;
+        // the normals are transformed by the inverse transpose, which keeps them outwards,
+        // while a mirroring transform turns dpdu x dpdv inwards, so dpdv is turned back
+        let normal_matrix = self.inverse.transpose();
+        let to_normal = |n: Vec3| normal_matrix.transform_vector(n).normalize();
+        let mirror = if self.transform.determinant() < 0.0 {
+            -1.0
+        } else {
+            1.0
+        };
+        Some(HitRecord {
+            p: self.transform.transform_point(rec.p),
+            normal: to_normal(rec.normal),
+            geometric_normal: to_normal(rec.geometric_normal),
+            dpdu: self.transform.transform_vector(rec.dpdu),
+            dpdv: mirror * self.transform.transform_vector(rec.dpdv),
+            ..rec
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::Sphere;
+    use cgmath::Deg;
+
+    /// the unit sphere at the origin under the transform
+    fn instance(transform: Matrix4<f32>) -> Instance {
+        let sphere = Sphere {
+            center: Vec3::new(0.0, 0.0, 0.0),
+            radius: 1.0,
+            mat: Arc::new(Diffuse {
+                albedo: Vec3::new(0.5, 0.5, 0.5),
+            }),
+        };
+        Instance::new(Arc::new(sphere), transform).unwrap()
+    }
+
+    fn ray(o: Pt3, d: Vec3) -> Ray {
+        Ray { o, d, time: 0.0 }
+    }
+
+    fn assert_hit(rec: &HitRecord, t: f32, p: Pt3, normal: Vec3, front_face: bool) {
+        assert!((rec.t - t).abs() < 1e-4, "t {} != {}", rec.t, t);
+        assert!((rec.p - p).magnitude() < 1e-4, "p {:?} != {:?}", rec.p, p);
+        assert!(
+            (rec.normal - normal).magnitude() < 1e-4,
+            "normal {:?} != {:?}",
+            rec.normal,
+            normal
+        );
+        assert_eq!(rec.front_face, front_face);
+        assert!(rec.dpdu.cross(rec.dpdv).dot(rec.normal) > 0.0);
+    }
+
+    #[test]
+    fn translated_scaled_rotated_sphere() {
+        // stretched along x, which is turned to z, an ellipsoid of the semi-axes 1, 1, 2 at z = -5
+        let transform = Matrix4::from_translation(Vec3::new(0.0, 0.0, -5.0))
+            * Matrix4::from_angle_y(Deg(90.0))
+            * Matrix4::from_nonuniform_scale(2.0, 1.0, 1.0);
+        let obj = instance(transform);
+        let rec = obj
+            .hit(
+                &ray(Pt3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0)),
+                T_MIN,
+                T_MAX,
+            )
+            .unwrap();
+        assert_hit(
+            &rec,
+            3.0,
+            Pt3::new(0.0, 0.0, -3.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            true,
+        );
+        // off the axis, the normal is along the gradient (x, 0, (z + 5) / 4)
+        let z = 3.0f32.sqrt();
+        let rec = obj
+            .hit(
+                &ray(Pt3::new(0.5, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0)),
+                T_MIN,
+                T_MAX,
+            )
+            .unwrap();
+        let normal = Vec3::new(0.5, 0.0, z / 4.0).normalize();
+        assert_hit(&rec, 5.0 - z, Pt3::new(0.5, 0.0, z - 5.0), normal, true);
+        // from the center, the parameter is in the unit of the direction
+        let r = ray(Pt3::new(0.0, 0.0, -5.0), Vec3::new(2.0, 0.0, 0.0));
+        let rec = obj.hit(&r, T_MIN, T_MAX).unwrap();
+        assert_hit(
+            &rec,
+            0.5,
+            Pt3::new(1.0, 0.0, -5.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            false,
+        );
+    }
+
+    #[test]
+    fn mirrored_sphere() {
+        let obj = instance(Matrix4::from_nonuniform_scale(-1.0, 1.0, 1.0));
+        let r = ray(Pt3::new(0.3, 0.2, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = obj.hit(&r, T_MIN, T_MAX).unwrap();
+        let z = (1.0f32 - 0.13).sqrt();
+        assert_hit(
+            &rec,
+            5.0 - z,
+            Pt3::new(0.3, 0.2, z),
+            Vec3::new(0.3, 0.2, z),
+            true,
+        );
+    }
+}
@@ -0,0 +1,161 @@
+use crate::tonemap::ToneMapper;
+use crate::*;
+use exr::prelude::{Encoding, SpecificChannels, WritableImage};
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+
+/// an image of linear radiance, in row-major order from the top-left corner
+pub struct Image {
+    pub width: i32,
+    pub height: i32,
+    pub pixels: Vec<Color>,
+}
+
+impl Image {
+    pub fn new(width: i32, height: i32) -> Self {
+        Image {
+            width,
+            height,
+            pixels: vec![Color::new(0.0, 0.0, 0.0); (width * height) as usize],
+        }
+    }
+
+    pub fn get(&self, x: i32, y: i32) -> Color {
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    pub fn set(&mut self, x: i32, y: i32, col: Color) {
+        self.pixels[(y * self.width + x) as usize] = col;
+    }
+
+    /// copy the image into this one, with its top-left corner at (x0, y0)
+    pub fn paste(&mut self, image: &Image, x0: i32, y0: i32) {
+        for y in 0..image.height {
+            for x in 0..image.width {
+                self.set(x0 + x, y0 + y, image.get(x, y));
+            }
+        }
+    }
+}
+
+/// the file formats of the output image
+#[derive(Clone, Copy)]
+pub enum Format {
+    /// binary PPM, i.e. P6
+    Ppm,
+    Png,
+    /// portable float map, of the linear radiance
+    Pfm,
+    /// single-layer OpenEXR, of the linear radiance
+    Exr,
+}
+
+impl Format {
+    /// decide the format by the extension of the file
+    pub fn from_path(path: &str) -> io::Result<Format> {
+        let ext = Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase());
+        match ext.as_deref() {
+            Some("ppm") => Ok(Format::Ppm),
+            Some("png") => Ok(Format::Png),
+            Some("pfm") => Ok(Format::Pfm),
+            Some("exr") => Ok(Format::Exr),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unsupported image format: {}", path),
+            )),
+        }
+    }
+}
+
+type Writer = BufWriter<File>;
+
+fn write_ppm(w: &mut Writer, image: &Image, tone_mapper: &ToneMapper) -> io::Result<()> {
+    write!(w, "P6\n{} {}\n255\n", image.width, image.height)?;
+    w.write_all(&tone_mapper.to_rgb8(image))
+}
+
+fn write_png(w: &mut Writer, image: &Image, tone_mapper: &ToneMapper) -> io::Result<()> {
+    let mut encoder = png::Encoder::new(w, image.width as u32, image.height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(io::Error::other)?;
+    writer
+        .write_image_data(&tone_mapper.to_rgb8(image))
+        .map_err(io::Error::other)
+}
+
+// see: https://www.pauldebevec.com/Research/HDR/PFM/
+fn write_pfm(w: &mut Writer, image: &Image, _: &ToneMapper) -> io::Result<()> {
+    // the negative scale stands for little-endian
+    write!(w, "PF\n{} {}\n-1.0\n", image.width, image.height)?;
+    // the scanlines are from the bottom to the top
+    for y in (0..image.height).rev() {
+        for x in 0..image.width {
+            let col = image.get(x, y);
+            for c in [col.x, col.y, col.z] {
+                w.write_all(&c.to_le_bytes())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_exr(path: &str, image: &Image) -> io::Result<()> {
+    let channels = SpecificChannels::rgb(|exr::math::Vec2(x, y)| {
+        let col = image.get(x as i32, y as i32);
+        (col.x, col.y, col.z)
+    });
+    // zip compressed scanlines, which is supported by most of the readers
+    exr::image::Image::from_encoded_channels(
+        (image.width as usize, image.height as usize),
+        Encoding::SMALL_LOSSLESS,
+        channels,
+    )
+    .write()
+    .to_file(path)
+    .map_err(io::Error::other)
+}
+
+/// read a PNG image, the 8-bit values are scaled to [0, 1] as they are,
+/// without decoding the sRGB curve
+pub fn read_png(path: &str) -> io::Result<Image> {
+    let mut decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+    // to 8-bit gray, gray-alpha, RGB or RGBA
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(io::Error::other)?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).map_err(io::Error::other)?;
+    let channels = info.color_type.samples();
+    let mut image = Image::new(info.width as i32, info.height as i32);
+    for (p, px) in image.pixels.iter_mut().zip(buf.chunks(channels)) {
+        let c = |i: usize| px[i] as f32 / 255.0;
+        *p = match info.color_type {
+            png::ColorType::Grayscale | png::ColorType::GrayscaleAlpha => {
+                Color::new(c(0), c(0), c(0))
+            }
+            _ => Color::new(c(0), c(1), c(2)),
+        };
+    }
+    Ok(image)
+}
+
+/// write the image to a file, the format is decided by the extension
+/// PPM and PNG are tone mapped 8-bit sRGB images,
+/// while PFM and EXR keep the linear radiance in 32-bit floats
+pub fn write_image(path: &str, image: &Image, tone_mapper: &ToneMapper) -> io::Result<()> {
+    let write = match Format::from_path(path)? {
+        Format::Ppm => write_ppm,
+        Format::Png => write_png,
+        Format::Pfm => write_pfm,
+        // the exr encoder manages the file by itself
+        Format::Exr => return write_exr(path, image),
+    };
+    let mut w = BufWriter::new(File::create(path)?);
+    write(&mut w, image, tone_mapper)?;
+    w.flush()
+}
@@ -7,7 +7,10 @@ use crate::*;
 use cgmath::prelude::*;
 use cgmath::Vector2;
 use rand::prelude::*;
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::f32::consts::PI;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 /// the RGB spectrum, R, G, B respectively
 pub type RGBSpectrum = Vec3;
@@ -23,23 +26,30 @@ impl LSampleRec {
     pub fn new(ray: &Ray, radiance: RGBSpectrum, p: f32) -> Self {
         LSampleRec {
             ray: *ray,
-            radiance: radiance,
-            p: p,
+            radiance,
+            p,
         }
     }
 }
 
 /// trait of light sources
-pub trait Light {
+/// lights are shared by all the rendering threads, so sampling must not mutate them
+pub trait Light: Send + Sync {
     /// test if the hit point is visible with the light, return the radiance if so
-    fn visible(&mut self, hit_point: Pt3, normal: Vec3, world: &HitableList)
-        -> Option<RGBSpectrum>;
+    fn visible(
+        &self,
+        hit_point: Pt3,
+        normal: Vec3,
+        time: f32,
+        world: &HitableList,
+    ) -> Option<RGBSpectrum>;
 
     /// visible function, for distributed raytracing
     fn visible_d(
-        &mut self,
+        &self,
         hit_point: Pt3,
         normal: Vec3,
+        time: f32,
         world: &HitableList,
     ) -> Option<LSampleRec>;
 
@@ -58,9 +68,10 @@ pub struct PointLight {
 
 impl Light for PointLight {
     fn visible(
-        &mut self,
+        &self,
         hit_point: Pt3,
         normal: Vec3,
+        time: f32,
         world: &HitableList,
     ) -> Option<RGBSpectrum> {
         // a ray from the hitting point to the light origin
@@ -68,6 +79,7 @@ impl Light for PointLight {
         let r = Ray {
             o: hit_point,
             d: dir,
+            time,
         };
         // if hit something , then it is invisible
         // tmax > 1.0 means that the hit point is behind the light source
@@ -81,16 +93,18 @@ impl Light for PointLight {
     }
 
     fn visible_d(
-        &mut self,
+        &self,
         hit_point: Pt3,
         normal: Vec3,
+        time: f32,
         world: &HitableList,
     ) -> Option<LSampleRec> {
         let r = Ray {
             o: hit_point,
             d: self.origin - hit_point,
+            time,
         };
-        self.visible(hit_point, normal, world)
+        self.visible(hit_point, normal, time, world)
             .map(|s| LSampleRec::new(&r, s, ZERO))
     }
 
@@ -108,34 +122,59 @@ pub struct DiskLight {
     pub radius: f32,
     pub spectrum: RGBSpectrum,
     pub sampler_kind: SamplerKind,
-    sampler: Option<Box<dyn AreaSampler>>,
+    /// the number of samples in a sequence of the sampler
+    pub sample_num: i32,
+    id: usize,
+}
+
+/// the counter to give each disk light an unique id
+static DISK_LIGHT_ID: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    /// the samplers of the disk lights, keyed by the light id
+    /// a sampler is a sequence of samples and not thread-safe,
+    /// so each rendering thread keeps its own sequence for every disk light
+    static DISK_SAMPLERS: RefCell<HashMap<usize, Box<dyn AreaSampler>>> =
+        RefCell::new(HashMap::new());
 }
 
 impl DiskLight {
-    fn init_sampler(&mut self) {
-        if self.sampler.as_ref().map_or(false, |x| x.has_next()) {
-            return;
-        }
-        // sample in disk of self.radius
-        let sampler: Box<dyn AreaSampler> = match self.sampler_kind {
-            SamplerKind::WhiteNoise => Box::new(WhiteNoiseSampler::new(self.radius * 2.0, NS2)),
-            SamplerKind::Uniform => Box::new(UniformSampler::new(self.radius * 2.0, NS2)),
-            SamplerKind::Jittered => Box::new(JitteredSampler::new(self.radius * 2.0, NS2)),
-            SamplerKind::BlueNoise => Box::new(BlueNoiseSampler::new(self.radius * 2.0, NS2, true)),
-        };
-        self.sampler = Some(sampler);
+    /// create a new sampler, in disk of self.radius
+    fn new_sampler(&self) -> Box<dyn AreaSampler> {
+        self.sampler_kind
+            .create(self.radius * 2.0, self.sample_num, true)
     }
 
-    pub fn new(origin: Pt3, radius: f32, spectrum: RGBSpectrum, sampler_kind: SamplerKind) -> Self {
-        let mut res = DiskLight {
-            origin: origin,
-            radius: radius,
-            spectrum: spectrum,
-            sampler_kind: sampler_kind,
-            sampler: None,
-        };
-        res.init_sampler();
-        res
+    /// take the next sample from the sequence of the current thread,
+    /// a new sequence is started when the last one runs out
+    fn next_sample(&self) -> Option<(f32, f32)> {
+        DISK_SAMPLERS.with(|samplers| {
+            let mut samplers = samplers.borrow_mut();
+            let sampler = samplers
+                .entry(self.id)
+                .or_insert_with(|| self.new_sampler());
+            if !sampler.has_next() {
+                *sampler = self.new_sampler();
+            }
+            sampler.sample_in_disk()
+        })
+    }
+
+    pub fn new(
+        origin: Pt3,
+        radius: f32,
+        spectrum: RGBSpectrum,
+        sampler_kind: SamplerKind,
+        sample_num: i32,
+    ) -> Self {
+        DiskLight {
+            origin,
+            radius,
+            spectrum,
+            sampler_kind,
+            sample_num,
+            id: DISK_LIGHT_ID.fetch_add(1, Ordering::Relaxed),
+        }
     }
 
     // see: https://www.pbr-book.org/3ed-2018/Light_Transport_I_Surface_Reflection/Sampling_Light_Sources
@@ -148,16 +187,16 @@ impl DiskLight {
 
 impl Light for DiskLight {
     fn visible(
-        &mut self,
+        &self,
         hit_point: Pt3,
         normal: Vec3,
+        time: f32,
         world: &HitableList,
     ) -> Option<RGBSpectrum> {
         // actually it's an integral, here use Monte Carlo
         // TODO: refactor
         let mut radiance = BLACK;
-        self.init_sampler();
-        let sampler = self.sampler.as_mut().unwrap();
+        let mut sampler = self.new_sampler();
         let mut actual_sample_num = 0;
         while let Some((a, b)) = sampler.sample_in_disk() {
             actual_sample_num += 1;
@@ -166,6 +205,7 @@ impl Light for DiskLight {
             let r = Ray {
                 o: hit_point,
                 d: dir,
+                time,
             };
             if world.hit(&r, T_MIN, 1.0 - T_MIN).is_none() {
                 radiance += self.spectrum * dir.normalize().dot(normal.normalize()) / dir.dot(dir)
@@ -181,21 +221,21 @@ impl Light for DiskLight {
     }
 
     fn visible_d(
-        &mut self,
+        &self,
         hit_point: Pt3,
         normal: Vec3,
+        time: f32,
         world: &HitableList,
     ) -> Option<LSampleRec> {
         let mut ret: Option<LSampleRec> = None;
         // sample in disk of self.radius
-        self.init_sampler();
-        let sampler = self.sampler.as_mut().unwrap();
-        if let Some((a, b)) = sampler.sample_in_disk() {
+        if let Some((a, b)) = self.next_sample() {
             let origin = self.origin + Vec3::new(a - self.radius, 0.0, b - self.radius);
             let dir = origin - hit_point;
             let r = Ray {
                 o: hit_point,
                 d: dir,
+                time,
             };
             if world.hit(&r, T_MIN, 1.0 - T_MIN).is_none() {
                 let cos = dir.normalize().dot(normal.normalize());
@@ -244,12 +284,14 @@ pub struct PolygonLight {
     spectrum: RGBSpectrum,
     area: f32,
     normal: Vec3,
+    /// the number of samples on each triangle for `visible`
+    pub sample_num: i32,
 }
 
 impl PolygonLight {
     /// XXX: only 2D mesh is supported,
     /// that is all normal vectors should be the same
-    pub fn new(mesh: MeshT, spectrum: RGBSpectrum) -> Self {
+    pub fn new(mesh: MeshT, spectrum: RGBSpectrum, sample_num: i32) -> Self {
         // let area = mesh.face_list.iter().fold(0.0, |sum, x| sum + x.area());
         let mut area = 0.0;
         let normal = mesh.face_list[0].normal();
@@ -261,10 +303,11 @@ impl PolygonLight {
             );
         }
         PolygonLight {
-            mesh: mesh,
-            spectrum: spectrum,
-            area: area,
-            normal: normal,
+            mesh,
+            spectrum,
+            area,
+            normal,
+            sample_num,
         }
     }
 }
@@ -290,24 +333,48 @@ fn sample_in_triangle(triangle: &Triangle) -> Pt3 {
 /// implement light for mesh (polygon light)
 impl Light for PolygonLight {
     fn visible(
-        &mut self,
+        &self,
         hit_point: Pt3,
         normal: Vec3,
+        time: f32,
         world: &HitableList,
     ) -> Option<RGBSpectrum> {
-        // TODO
-        assert!(false);
-        None
+        // the integral over the triangles by Monte Carlo, as the disk light does
+        let mut radiance = BLACK;
+        for t in self.mesh.face_list.iter() {
+            let mut sum = BLACK;
+            for _ in 0..self.sample_num {
+                let dir = sample_in_triangle(t) - hit_point;
+                let r = Ray {
+                    o: hit_point,
+                    d: dir,
+                    time,
+                };
+                let cos = dir.normalize().dot(normal.normalize());
+                if cos > 0.0 && world.hit(&r, T_MIN, 1.0 - T_MIN).is_none() {
+                    // the light emits from both sides, as `hit` does
+                    let cos_light = dir.normalize().dot(self.normal).abs();
+                    sum += self.spectrum * cos * cos_light / dir.dot(dir);
+                }
+            }
+            radiance += sum * t.area() / self.sample_num as f32;
+        }
+        if radiance != BLACK {
+            Some(radiance)
+        } else {
+            None
+        }
     }
 
     fn visible_d(
-        &mut self,
+        &self,
         hit_point: Pt3,
         normal: Vec3,
+        time: f32,
         world: &HitableList,
     ) -> Option<LSampleRec> {
         // randomly select a triangle face
-        let t = self.mesh.face_list.choose_mut(&mut rand::thread_rng())?;
+        let t = self.mesh.face_list.choose(&mut rand::thread_rng())?;
         // get a uniform sample point on it
         let origin = sample_in_triangle(t);
         // generate a ray from the hitting point
@@ -315,6 +382,7 @@ impl Light for PolygonLight {
         let r = Ray {
             o: hit_point,
             d: dir,
+            time,
         };
         // TODO: check hitting other lights also
         if world.hit(&r, T_MIN, 1.0 - T_MIN).is_none() {
@@ -342,7 +410,9 @@ impl Light for PolygonLight {
     }
 
     fn hit(&self, r: &Ray) -> Option<(RGBSpectrum, f32)> {
-        self.mesh.hit_both_side(r, T_MIN, T_MAX).map(|r| (self.spectrum, r.t))
+        self.mesh
+            .hit_both_side(r, T_MIN, T_MAX)
+            .map(|r| (self.spectrum, r.t))
     }
 }
 
@@ -352,28 +422,29 @@ pub struct LightList {
 }
 
 impl LightList {
-    fn _hit(&self, r: &Ray) -> Option<(RGBSpectrum, f32, &Box<dyn Light>)> {
+    fn _hit(&self, r: &Ray) -> Option<(RGBSpectrum, f32, &dyn Light)> {
         // TODO: refactor
         for l in &self.list {
             let t = l.hit(r);
             if t.is_some() {
-                return t.map(|(f, s)| (f, s, l));
+                return t.map(|(f, s)| (f, s, l.as_ref()));
             }
         }
-        return None;
+        None
     }
 }
 
 impl Light for LightList {
     fn visible(
-        &mut self,
+        &self,
         hit_point: Pt3,
         normal: Vec3,
+        time: f32,
         world: &HitableList,
     ) -> Option<RGBSpectrum> {
         let mut res = BLACK;
-        for l in &mut self.list {
-            if let Some(r) = l.visible(hit_point, normal, world) {
+        for l in &self.list {
+            if let Some(r) = l.visible(hit_point, normal, time, world) {
                 res += r;
             }
         }
@@ -385,15 +456,16 @@ impl Light for LightList {
     }
 
     fn visible_d(
-        &mut self,
+        &self,
         hit_point: Pt3,
         normal: Vec3,
+        time: f32,
         world: &HitableList,
     ) -> Option<LSampleRec> {
         // randomly pick a light in the list, and apply the corresponding `visible`
         self.list
-            .choose_mut(&mut rand::thread_rng())
-            .and_then(|e| e.visible_d(hit_point, normal, world))
+            .choose(&mut rand::thread_rng())
+            .and_then(|e| e.visible_d(hit_point, normal, time, world))
     }
 
     fn pdf(&self, r: &Ray) -> f32 {
@@ -7,7 +7,8 @@ use cgmath::prelude::*;
 use rand::prelude::*;
 use std::f32::consts::PI;
 
-pub trait Material {
+/// materials are shared by all the rendering threads
+pub trait Material: Send + Sync {
     /// create a scattered ray, or None
     fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<Ray>;
 
@@ -25,7 +26,12 @@ pub trait Material {
     /// how much the ray should be attenuated
     fn attenuation(&self) -> RGBSpectrum;
 
-    fn do_material(&self, r: &Ray, rec: &HitRecord, world: &mut World, depth: i32) -> RGBSpectrum;
+    /// the overall color of the surface, for the feature buffers of the denoiser
+    fn albedo(&self) -> RGBSpectrum {
+        self.attenuation()
+    }
+
+    fn do_material(&self, r: &Ray, rec: &HitRecord, world: &World, depth: i32) -> RGBSpectrum;
 }
 
 #[derive(Clone)]
@@ -73,6 +79,7 @@ impl Material for Metal {
         let scattered = Ray {
             o: rec.p,
             d: reflected,
+            time: r_in.time,
         };
         // TODO: figure out the formula
         if scattered.d.dot(rec.normal) > f32::EPSILON {
@@ -95,7 +102,7 @@ impl Material for Metal {
     }
 
     fn pdf(&self, din: Vec3, dout: Vec3, dnor: Vec3) -> f32 {
-        if let Some(_) = self._brdf(din, dout, dnor) {
+        if self._brdf(din, dout, dnor).is_some() {
             1.0
         } else {
             ZERO
@@ -109,7 +116,7 @@ impl Material for Metal {
     // HACK: This method is only for the MIS shader, they are mutual recursive
     // because the calculation for the microfacet model is slightly different from others
     // it caculates the corresponding light value according to the material's BRDF
-    fn do_material(&self, r: &Ray, rec: &HitRecord, world: &mut World, depth: i32) -> RGBSpectrum {
+    fn do_material(&self, r: &Ray, rec: &HitRecord, world: &World, depth: i32) -> RGBSpectrum {
         do_material_default(self, r, rec, world, depth)
     }
 }
@@ -141,13 +148,17 @@ impl Material for Diffuse {
         None
     }
 
-    fn scatter_d(&self, _r_in: &Ray, rec: &HitRecord) -> Option<Ray> {
+    fn scatter_d(&self, r_in: &Ray, rec: &HitRecord) -> Option<Ray> {
         // generate a random unit vector, in the semisphere of the normal vec
         let mut d = unit_vec_on_sphere();
         if d.dot(rec.normal) < 0.0 {
             d = -d; // semisphere
         }
-        Some(Ray { o: rec.p, d: d })
+        Some(Ray {
+            o: rec.p,
+            d,
+            time: r_in.time,
+        })
     }
 
     fn brdf(&self, _din: Vec3, _dout: Vec3, _dnor: Vec3) -> RGBSpectrum {
@@ -163,12 +174,14 @@ impl Material for Diffuse {
         self.albedo
     }
 
-    fn do_material(&self, r: &Ray, rec: &HitRecord, world: &mut World, depth: i32) -> RGBSpectrum {
+    fn do_material(&self, r: &Ray, rec: &HitRecord, world: &World, depth: i32) -> RGBSpectrum {
         do_material_default(self, r, rec, world, depth)
     }
 }
 
-fn refract(v: Vec3, n: Vec3, ni_over_nt: f32) -> Option<Vec3> {
+/// the refracted direction of `v` through the surface of normal `n` facing `v`,
+/// `None` for the total internal reflection
+pub(crate) fn refract(v: Vec3, n: Vec3, ni_over_nt: f32) -> Option<Vec3> {
     let uv = v.normalize();
     let dt = uv.dot(n);
     let d = 1.0 - ni_over_nt * ni_over_nt * (1.0 - dt * dt);
@@ -222,7 +235,7 @@ impl Material for Dielectric {
         let reflected = reflect(r_in.d, rec.normal);
         let outward_normal;
         let ni_over_nt;
-        if r_in.d.dot(rec.normal) > T_MIN {
+        if !rec.front_face {
             outward_normal = -rec.normal;
             ni_over_nt = self.ref_idx;
         } else {
@@ -233,10 +246,12 @@ impl Material for Dielectric {
             Some(refracted) => Some(Ray {
                 o: rec.p,
                 d: refracted,
+                time: r_in.time,
             }),
             None => Some(Ray {
                 o: rec.p,
                 d: reflected,
+                time: r_in.time,
             }),
         }
     }
@@ -254,7 +269,7 @@ impl Material for Dielectric {
     }
 
     fn pdf(&self, din: Vec3, dout: Vec3, dnor: Vec3) -> f32 {
-        if let Some(_) = self._brdf(din, dout, dnor) {
+        if self._brdf(din, dout, dnor).is_some() {
             1.0
         } else {
             ZERO
@@ -265,7 +280,7 @@ impl Material for Dielectric {
         RGBSpectrum::new(1.0, 1.0, 1.0)
     }
 
-    fn do_material(&self, r: &Ray, rec: &HitRecord, world: &mut World, depth: i32) -> RGBSpectrum {
+    fn do_material(&self, r: &Ray, rec: &HitRecord, world: &World, depth: i32) -> RGBSpectrum {
         do_material_default(self, r, rec, world, depth)
     }
 }
@@ -325,8 +340,8 @@ fn get_local_coordinate(v1: Vec3) -> (Vec3, Vec3, Vec3) {
 // see: https://computergraphics.stackexchange.com/questions/7656/importance-sampling-microfacet-ggx
 // wg: geometry normal, wm: halfway vector
 impl Material for Microfacet {
+    /// the whitted shader lights a microfacet surface like a diffuse one, by the direct light
     fn scatter(&self, _r_in: &Ray, _rec: &HitRecord) -> Option<Ray> {
-        assert!(false, "not supported");
         None
     }
 
@@ -355,7 +370,11 @@ impl Material for Microfacet {
         // calculate the in direction with the reflection law
         let wo = r_in.d;
         let wi = reflect(wo, wm);
-        Some(Ray { o: rec.p, d: wi })
+        Some(Ray {
+            o: rec.p,
+            d: wi,
+            time: r_in.time,
+        })
     }
 
     fn brdf(&self, din: Vec3, dout: Vec3, dnor: Vec3) -> RGBSpectrum {
@@ -390,16 +409,20 @@ impl Material for Microfacet {
         (d * cos_theta) / (4.0 * wo.dot(wm).abs())
     }
 
-    // TODO: consider to remove this method
+    /// only the whitted and the plain path tracing shaders use it, weighing the glossy reflection
+    /// by the overall color, while the MIS shader evaluates the full BRDF in `do_material`
     fn attenuation(&self) -> RGBSpectrum {
-        assert!(false);
-        BLACK
+        self.albedo()
+    }
+
+    fn albedo(&self) -> RGBSpectrum {
+        self.attenuation * (1.0 - self.metallic) + self.f0 * self.metallic
     }
 
     // see: http://www.codinglabs.net/article_physically_based_rendering_cook_torrance.aspx
-    fn do_material(&self, r: &Ray, rec: &HitRecord, world: &mut World, depth: i32) -> RGBSpectrum {
+    fn do_material(&self, r: &Ray, rec: &HitRecord, world: &World, depth: i32) -> RGBSpectrum {
         // the specular part
-        let specular = match self.scatter_d(&r, rec) {
+        let specular = match self.scatter_d(r, rec) {
             // for scatter case, the result is only dependent on the scattered ray
             Some(scattered) => {
                 let li = path_trace_shader_mis(&scattered, world, depth + 1);
@@ -418,7 +441,11 @@ impl Material for Microfacet {
             if d.dot(rec.normal) < 0.0 {
                 d = -d; // semisphere
             }
-            let scattered = Ray { o: rec.p, d: d };
+            let scattered = Ray {
+                o: rec.p,
+                d,
+                time: r.time,
+            };
 
             let wi = scattered.d;
             let wg = rec.normal;
@@ -430,7 +457,7 @@ impl Material for Microfacet {
             // calling the recursive shader again is far too heavy for "path tracing"
             // here just confine the depth to no more than `5` for a performance trade-off
             // This may work because the weight of diffuse is not much
-            let depth = depth.max(34);
+            let depth = depth.max(world.max_depth - 6);
             let li = path_trace_shader_mis(&scattered, world, depth + 1);
             let l_pdf = world.lights.pdf(&scattered);
             let b_pdf = 1.0 / PI;
@@ -438,7 +465,10 @@ impl Material for Microfacet {
         };
         let brdf = mul_v(&kd, &diffuse) + specular;
 
-        let direct = match world.lights.visible_d(rec.p, rec.normal, &world.objects) {
+        let direct = match world
+            .lights
+            .visible_d(rec.p, rec.normal, r.time, &world.objects)
+        {
             Some(LSampleRec { ray, radiance, p }) => {
                 let b_pdf = self.pdf(r.d, ray.d, rec.normal);
                 mul_v(&radiance, &self.brdf(r.d, ray.d, rec.normal)) / (p + b_pdf)
@@ -455,10 +485,10 @@ fn do_material_default<M: Material>(
     m: &M,
     r: &Ray,
     rec: &HitRecord,
-    world: &mut World,
+    world: &World,
     depth: i32,
 ) -> RGBSpectrum {
-    let brdf = match m.scatter_d(&r, rec) {
+    let brdf = match m.scatter_d(r, rec) {
         // for scatter case, the result is only dependent on the scattered ray
         Some(scattered) => {
             let li = path_trace_shader_mis(&scattered, world, depth + 1);
@@ -470,7 +500,10 @@ fn do_material_default<M: Material>(
         None => BLACK,
     };
 
-    let direct = match world.lights.visible_d(rec.p, rec.normal, &world.objects) {
+    let direct = match world
+        .lights
+        .visible_d(rec.p, rec.normal, r.time, &world.objects)
+    {
         Some(LSampleRec { ray, radiance, p }) => {
             let b_pdf = m.pdf(r.d, ray.d, rec.normal);
             mul_v(&radiance, &m.brdf(r.d, ray.d, rec.normal)) / (p + b_pdf)
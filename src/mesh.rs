@@ -9,7 +9,7 @@ use rand::prelude::*;
 use std::cmp::*;
 
 pub trait FromFaceList {
-    fn from_face_list(list: &Vec<Triangle>) -> Self;
+    fn from_face_list(list: &[Triangle]) -> Self;
 }
 
 /// Mesh is a struct with a Hitable list and an accelerate structure
@@ -27,9 +27,9 @@ impl<T: FromFaceList> Mesh<T> {
             z: Deg(z),
         });
         let d = Decomposed {
-            scale: scale,
+            scale,
             rot: rotation,
-            disp: disp,
+            disp,
         };
 
         for f in &mut self.face_list {
@@ -89,7 +89,7 @@ impl Hitable for NaiveMesh {
 }
 
 impl FromFaceList for Naive {
-    fn from_face_list(_list: &Vec<Triangle>) -> Self {
+    fn from_face_list(_list: &[Triangle]) -> Self {
         Naive
     }
 }
@@ -114,7 +114,8 @@ impl BoundingBox {
             let t1 = ((self.min[a] - r.o[a]) / r.d[a]).max((self.max[a] - r.o[a]) / r.d[a]);
             tmin = t0.max(tmin);
             tmax = t1.min(tmax);
-            if tmax <= tmin {
+            // the box of an axis-aligned triangle is flat, and still hit where tmax == tmin
+            if tmax < tmin {
                 return false;
             }
         }
@@ -132,7 +133,7 @@ impl BoundingBox {
             max[a] = max[a].max(t.vertex.2[a]);
             min[a] = min[a].min(t.vertex.2[a]);
         }
-        BoundingBox { max: max, min: min }
+        BoundingBox { max, min }
     }
 
     fn surrounding_box(b0: BoundingBox, b1: BoundingBox) -> BoundingBox {
@@ -154,7 +155,7 @@ impl BoundingBox {
 }
 
 impl FromFaceList for BoundingBox {
-    fn from_face_list(list: &Vec<Triangle>) -> Self {
+    fn from_face_list(list: &[Triangle]) -> Self {
         let mut min = Vec3::new(T_MAX, T_MAX, T_MAX);
         let mut max = -min;
         for t in list {
@@ -171,7 +172,7 @@ impl FromFaceList for BoundingBox {
             "Box: max({}, {}, {}), min({}, {}, {})",
             max[0], max[1], max[2], min[0], min[1], min[2]
         );
-        BoundingBox { max: max, min: min }
+        BoundingBox { max, min }
     }
 }
 
@@ -222,7 +223,7 @@ impl BVHTree {
         }
     }
 
-    fn build_tree(face_list: &Vec<(BoundingBox, usize)>) -> Self {
+    fn build_tree(face_list: &[(BoundingBox, usize)]) -> Self {
         let left;
         let right;
         let axis = rand3() as usize;
@@ -243,12 +244,12 @@ impl BVHTree {
                 }
             },
             _ => {
-                let mut new_list = face_list.clone();
+                let mut new_list = face_list.to_vec();
                 new_list.sort_by(|a, b| box_compare(a.0, b.0, axis));
                 let mid = new_list.len() / 2;
                 let (l, r) = new_list.split_at(mid);
-                left = BVHTree::build_tree(&l.to_vec());
-                right = BVHTree::build_tree(&r.to_vec());
+                left = BVHTree::build_tree(l);
+                right = BVHTree::build_tree(r);
             }
         }
 
@@ -256,23 +257,33 @@ impl BVHTree {
         BVHTree::Node {
             left: Box::new(left),
             right: Box::new(right),
-            aabb: aabb,
+            aabb,
         }
     }
 
     fn hit_tree(
         &self,
-        face_list: &Vec<Triangle>,
+        face_list: &[Triangle],
         r: &Ray,
         t_min: f32,
         t_max: f32,
     ) -> Option<HitRecord> {
+        self.hit_tree_with(r, t_min, t_max, &|i, r, t_min, t_max| {
+            face_list[i].hit(r, t_min, t_max)
+        })
+    }
+
+    /// `hit_leaf(i, r, t_min, t_max)` hits the i-th primitive the tree is built on
+    fn hit_tree_with<F>(&self, r: &Ray, t_min: f32, t_max: f32, hit_leaf: &F) -> Option<HitRecord>
+    where
+        F: Fn(usize, &Ray, f32, f32) -> Option<HitRecord>,
+    {
         match &self {
-            BVHTree::Leaf((_, i)) => face_list[*i].hit(r, t_min, t_max),
+            BVHTree::Leaf((_, i)) => hit_leaf(*i, r, t_min, t_max),
             BVHTree::Node { left, right, aabb } => {
                 if aabb.hit_box(r, t_min, t_max) {
-                    let hit_left = left.hit_tree(face_list, r, t_min, t_max);
-                    let hit_right = right.hit_tree(face_list, r, t_min, t_max);
+                    let hit_left = left.hit_tree_with(r, t_min, t_max, hit_leaf);
+                    let hit_right = right.hit_tree_with(r, t_min, t_max, hit_leaf);
                     match (hit_left, hit_right) {
                         (Some(left_rec), Some(right_rec)) => {
                             if left_rec.t < right_rec.t {
@@ -293,8 +304,28 @@ impl BVHTree {
     }
 }
 
+impl BVHTree {
+    /// push the indices of the primitives in the boxes the ray passes through,
+    /// an index may be pushed twice, as a leaf can be both children of a node
+    fn leaves_along(&self, r: &Ray, t_min: f32, t_max: f32, leaves: &mut Vec<usize>) {
+        match &self {
+            BVHTree::Leaf((aabb, i)) => {
+                if aabb.hit_box(r, t_min, t_max) {
+                    leaves.push(*i);
+                }
+            }
+            BVHTree::Node { left, right, aabb } => {
+                if aabb.hit_box(r, t_min, t_max) {
+                    left.leaves_along(r, t_min, t_max, leaves);
+                    right.leaves_along(r, t_min, t_max, leaves);
+                }
+            }
+        }
+    }
+}
+
 impl FromFaceList for BVHTree {
-    fn from_face_list(list: &Vec<Triangle>) -> Self {
+    fn from_face_list(list: &[Triangle]) -> Self {
         let box_list = list.iter().map(BoundingBox::triangle_bounding_box);
         let mlist: Vec<_> = box_list.zip(0..).map(|(a, b)| (a, b as usize)).collect();
         BVHTree::build_tree(&mlist)
@@ -307,3 +338,178 @@ impl Hitable for Mesh<BVHTree> {
             .hit_tree(&self.face_list, r, t_min, t_max)
     }
 }
+
+/// the relative distance of the hits on the neighbouring triangles, taken as the same hit
+const SAME_HIT: f32 = 1e-5;
+
+/// a closed mesh, whose triangles are wound counterclockwise as seen from the outside
+impl Solid for Mesh<BVHTree> {
+    fn hits(&self, r: &Ray, t_min: f32, t_max: f32) -> Vec<HitRecord> {
+        let mut faces = Vec::new();
+        self.acc_structure.leaves_along(r, t_min, t_max, &mut faces);
+        faces.sort_unstable();
+        faces.dedup();
+        // the ray leaves the mesh through the back faces
+        let mut hits: Vec<HitRecord> = faces
+            .into_iter()
+            .filter_map(|i| self.face_list[i].hit_both_side(r, t_min, t_max))
+            .collect();
+        sort_hits(&mut hits);
+        // a ray through an edge or a vertex hits each triangle around it,
+        // which is still one crossing of the surface
+        hits.dedup_by(|b, a| b.front_face == a.front_face && b.t - a.t < SAME_HIT * a.t.max(1.0));
+        hits
+    }
+}
+
+/// a mesh moving between two states, e.g. two `Mesh::transform` of the same mesh,
+/// the vertices move linearly from `face_list0` at `time0` to `face_list1` at `time1`
+pub struct MotionMesh {
+    pub face_list0: Vec<Triangle>,
+    pub face_list1: Vec<Triangle>,
+    pub time0: f32,
+    pub time1: f32,
+    /// built on the boxes covering both states, so on the whole motion
+    acc_structure: BVHTree,
+}
+
+impl MotionMesh {
+    /// the meshes should have the same faces in the same order
+    pub fn new(mesh0: FastMesh, mesh1: FastMesh, time0: f32, time1: f32) -> Self {
+        assert_eq!(mesh0.face_list.len(), mesh1.face_list.len());
+        let box_list: Vec<_> = mesh0
+            .face_list
+            .iter()
+            .zip(mesh1.face_list.iter())
+            .enumerate()
+            .map(|(i, (t0, t1))| {
+                let b = BoundingBox::surrounding_box(
+                    BoundingBox::triangle_bounding_box(t0),
+                    BoundingBox::triangle_bounding_box(t1),
+                );
+                (b, i)
+            })
+            .collect();
+        MotionMesh {
+            face_list0: mesh0.face_list,
+            face_list1: mesh1.face_list,
+            time0,
+            time1,
+            acc_structure: BVHTree::build_tree(&box_list),
+        }
+    }
+
+    /// the i-th face at the time
+    fn face(&self, i: usize, time: f32) -> Triangle {
+        let (t0, t1) = (&self.face_list0[i], &self.face_list1[i]);
+        let k = if self.time1 > self.time0 {
+            ((time - self.time0) / (self.time1 - self.time0)).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let lerp = |p0: Pt3, p1: Pt3| p0 + (p1 - p0) * k;
+        Triangle {
+            vertex: (
+                lerp(t0.vertex.0, t1.vertex.0),
+                lerp(t0.vertex.1, t1.vertex.1),
+                lerp(t0.vertex.2, t1.vertex.2),
+            ),
+            mat: t0.mat.clone(),
+        }
+    }
+}
+
+impl Hitable for MotionMesh {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        self.acc_structure
+            .hit_tree_with(r, t_min, t_max, &|i, r, t_min, t_max| {
+                self.face(i, r.time).hit(r, t_min, t_max)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::{Diffuse, Material};
+    use std::sync::Arc;
+
+    /// the cube [-1, 1]^3, each face split along a diagonal
+    fn cube() -> FastMesh {
+        let mat: Arc<dyn Material> = Arc::new(Diffuse {
+            albedo: Vec3::new(0.5, 0.5, 0.5),
+        });
+        let v = |i: usize| {
+            let c = |bit: usize| if i & bit == 0 { -1.0 } else { 1.0 };
+            Pt3::new(c(1), c(2), c(4))
+        };
+        let quads = [
+            [0, 2, 6, 4],
+            [1, 3, 7, 5],
+            [0, 1, 5, 4],
+            [2, 3, 7, 6],
+            [0, 1, 3, 2],
+            [4, 5, 7, 6],
+        ];
+        let mut face_list = Vec::new();
+        for [a, b, c, d] in quads {
+            for (p0, p1, p2) in [(v(a), v(b), v(c)), (v(a), v(c), v(d))] {
+                // wound counterclockwise as seen from the outside
+                let outwards = (p1 - p0).cross(p2 - p0).dot(p0.to_vec()) > 0.0;
+                let vertex = if outwards { (p0, p1, p2) } else { (p0, p2, p1) };
+                face_list.push(Triangle {
+                    vertex,
+                    mat: mat.clone(),
+                });
+            }
+        }
+        let acc_structure = FromFaceList::from_face_list(&face_list);
+        Mesh {
+            face_list,
+            acc_structure,
+        }
+    }
+
+    fn crossings(o: Pt3, d: Vec3) -> Vec<(f32, bool)> {
+        let r = Ray { o, d, time: 0.0 };
+        cube()
+            .hits(&r, T_MIN, T_MAX)
+            .iter()
+            .map(|h| (h.t, h.front_face))
+            .collect()
+    }
+
+    fn assert_near(actual: &[(f32, bool)], expected: &[(f32, bool)]) {
+        assert_eq!(
+            actual.len(),
+            expected.len(),
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+        for (a, e) in actual.iter().zip(expected) {
+            assert!(
+                (a.0 - e.0).abs() < 1e-4 && a.1 == e.1,
+                "{:?} != {:?}",
+                actual,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn ray_through_edges() {
+        // in through the edge x = z = 1, out through the edge x = z = -1
+        let hits = crossings(Pt3::new(3.0, 0.3, 3.0), Vec3::new(-1.0, 0.0, -1.0));
+        assert_near(&hits, &[(2.0, true), (4.0, false)]);
+        // across the diagonals splitting the faces
+        let hits = crossings(Pt3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        assert_near(&hits, &[(4.0, true), (6.0, false)]);
+    }
+
+    #[test]
+    fn ray_through_vertices() {
+        let hits = crossings(Pt3::new(3.0, 3.0, 3.0), Vec3::new(-1.0, -1.0, -1.0));
+        assert_near(&hits, &[(2.0, true), (4.0, false)]);
+    }
+}
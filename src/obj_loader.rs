@@ -5,7 +5,7 @@ use crate::*;
 use obj::{load_obj, Obj};
 use std::fs::File;
 use std::io::BufReader;
-use std::rc::Rc;
+use std::sync::Arc;
 
 /// choosing mesh implementations
 // type MeshT = NaiveMesh;
@@ -13,7 +13,12 @@ use std::rc::Rc;
 type MeshT = FastMesh;
 
 /// load an obj file, parse into a hitable
-pub fn load_obj_file(path: String, mat: impl Material + 'static + Clone) -> obj::ObjResult<MeshT> {
+pub fn load_obj_file(path: String, mat: impl Material + 'static) -> obj::ObjResult<MeshT> {
+    load_obj_file_shared(path, Arc::new(mat))
+}
+
+/// load an obj file, with a material shared with other objects
+pub fn load_obj_file_shared(path: String, mat: Arc<dyn Material>) -> obj::ObjResult<MeshT> {
     let file = File::open(path)?;
     let input = BufReader::new(file);
     let model: Obj<obj::Position, usize> = load_obj(input)?;
@@ -34,10 +39,9 @@ pub fn load_obj_file(path: String, mat: impl Material + 'static + Clone) -> obj:
                 Pt3::new(p1[0], p1[1], p1[2]),
                 Pt3::new(p2[0], p2[1], p2[2]),
             ),
-            // TODO: into reference
-            mat: Rc::new(mat.clone())
+            mat: mat.clone(),
         });
-        i = i + 3;
+        i += 3;
     }
     let acc = FromFaceList::from_face_list(&list);
     Ok(MeshT {
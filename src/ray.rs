@@ -4,9 +4,11 @@ use crate::*;
 pub struct Ray {
     pub o: Pt3,
     pub d: Vec3,
+    /// the time in the shutter interval
+    pub time: f32,
 }
 impl Ray {
     pub fn point_at_parameter(&self, t: f32) -> Pt3 {
         self.o + (t * self.d)
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,490 @@
+//! a camera of real lens elements, after the realistic camera of pbrt
+//!
+//! the lens space has the film at z = 0, the lens elements at z < 0 towards the scene,
+//! x to the right and y up, the same as the basis (u, v, w) of the other cameras
+
+use crate::camera::*;
+use crate::material::refract;
+use crate::ray::*;
+use crate::*;
+use cgmath::prelude::*;
+use std::fs;
+
+/// a spherical interface between two media, or the aperture stop
+#[derive(Clone, Copy, Debug)]
+pub struct LensElement {
+    /// positive if the center of the sphere is towards the film, 0 for the aperture stop
+    pub curvature_radius: f32,
+    /// the distance to the next element towards the film, or to the film for the last one
+    pub thickness: f32,
+    /// the index of refraction of the medium behind the element, 0 or 1 for the air
+    pub ior: f32,
+    pub aperture_radius: f32,
+}
+
+/// the lens elements and the film of a realistic camera
+#[derive(Clone, Debug)]
+pub struct Prescription {
+    /// from the front element to the rear one, in millimeters
+    pub elements: Vec<LensElement>,
+    /// the units of the scene in a millimeter
+    pub scale: f32,
+    /// the diagonal of the film in millimeters
+    pub film_diagonal: f32,
+    /// narrow the aperture stop to this diameter in millimeters
+    pub stop_diameter: Option<f32>,
+}
+
+/// read a lens prescription, one element per line from the front one to the rear one,
+/// `radius thickness ior aperture` in millimeters, the aperture being the diameter,
+/// `#` starts a comment
+/// the lens is scaled from millimeters to meters, on a 35mm film
+pub fn load_prescription(path: &str) -> Result<Prescription, String> {
+    let src = fs::read_to_string(path).map_err(|e| format!("cannot read `{}`: {}", path, e))?;
+    let mut elements = Vec::new();
+    for (i, line) in src.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("");
+        if line.trim().is_empty() {
+            continue;
+        }
+        let v: Vec<f32> = line
+            .split_whitespace()
+            .map(|s| s.parse::<f32>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| format!("{} line {}: expected numbers", path, i + 1))?;
+        if v.len() != 4 {
+            return Err(format!(
+                "{} line {}: expected `radius thickness ior aperture`",
+                path,
+                i + 1
+            ));
+        }
+        elements.push(LensElement {
+            curvature_radius: v[0],
+            thickness: v[1],
+            ior: v[2],
+            aperture_radius: 0.5 * v[3],
+        });
+    }
+    if elements.is_empty() {
+        return Err(format!("{} has no lens elements", path));
+    }
+    Ok(Prescription {
+        elements,
+        scale: 0.001,
+        film_diagonal: 35.0,
+        stop_diameter: None,
+    })
+}
+
+/// the number of the bins of the exit pupil bounds, from the center to the corner of the film
+const PUPIL_BINS: usize = 64;
+
+/// the bounds of the exit pupil, on the plane of the rear element
+#[derive(Clone, Copy, Debug)]
+struct PupilBounds {
+    x0: f32,
+    y0: f32,
+    x1: f32,
+    y1: f32,
+}
+
+impl PupilBounds {
+    fn area(&self) -> f32 {
+        (self.x1 - self.x0) * (self.y1 - self.y0)
+    }
+}
+
+pub struct RealisticCamera {
+    origin: Pt3,
+    /// the basis of the camera, `w` points backwards
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+    /// in the units of the scene, the last one is moved to focus
+    elements: Vec<LensElement>,
+    film_width: f32,
+    film_height: f32,
+    pupil: Vec<PupilBounds>,
+    pub shutter: Shutter,
+}
+
+// see: Pharr, Jakob and Humphreys, Physically Based Rendering, 3rd edition, 6.4
+impl RealisticCamera {
+    /// the camera of the lens, focused at `focus_dist` from the film,
+    /// fails if the lens cannot focus there
+    pub fn new(
+        lookfrom: Pt3,
+        lookat: Pt3,
+        vup: Vec3,
+        lens: &Prescription,
+        focus_dist: f32,
+        aspect: f32,
+    ) -> Result<RealisticCamera, String> {
+        let scale = lens.scale;
+        let w = (lookfrom - lookat).normalize();
+        let u = vup.cross(w).normalize();
+        let v = w.cross(u);
+        let mut elements: Vec<LensElement> = lens
+            .elements
+            .iter()
+            .map(|e| LensElement {
+                curvature_radius: e.curvature_radius * scale,
+                thickness: e.thickness * scale,
+                ior: e.ior,
+                aperture_radius: e.aperture_radius * scale,
+            })
+            .collect();
+        if let Some(d) = lens.stop_diameter {
+            match elements.iter_mut().find(|e| e.curvature_radius == 0.0) {
+                Some(stop) => stop.aperture_radius = stop.aperture_radius.min(0.5 * d * scale),
+                None => return Err(String::from("the lens has no aperture stop")),
+            }
+        }
+        let film_height = lens.film_diagonal * scale / (1.0 + aspect * aspect).sqrt();
+        let mut cam = RealisticCamera {
+            origin: lookfrom,
+            u,
+            v,
+            w,
+            elements,
+            film_width: aspect * film_height,
+            film_height,
+            pupil: Vec::new(),
+            shutter: Shutter::default(),
+        };
+        let thickness = cam.focus(focus_dist)?;
+        cam.elements.last_mut().unwrap().thickness = thickness;
+        cam.pupil = (0..PUPIL_BINS)
+            .map(|i| {
+                let r = 0.5 * cam.film_diagonal();
+                cam.bound_exit_pupil(
+                    i as f32 / PUPIL_BINS as f32 * r,
+                    (i + 1) as f32 / PUPIL_BINS as f32 * r,
+                )
+            })
+            .collect();
+        Ok(cam)
+    }
+
+    fn film_diagonal(&self) -> f32 {
+        (self.film_width * self.film_width + self.film_height * self.film_height).sqrt()
+    }
+
+    fn rear_z(&self) -> f32 {
+        self.elements.last().unwrap().thickness
+    }
+
+    fn front_z(&self) -> f32 {
+        self.elements.iter().map(|e| e.thickness).sum()
+    }
+
+    /// the ray of the lens space out of the front element, `None` if it is blocked
+    fn trace_from_film(&self, r: &Ray) -> Option<Ray> {
+        let mut r = *r;
+        let mut z = 0.0;
+        for (i, e) in self.elements.iter().enumerate().rev() {
+            z -= e.thickness;
+            let eta_i = medium(e.ior);
+            let eta_t = if i > 0 {
+                medium(self.elements[i - 1].ior)
+            } else {
+                1.0
+            };
+            r = self.pass(e, z, &r, eta_i, eta_t)?;
+        }
+        Some(r)
+    }
+
+    /// the ray of the lens space out of the rear element, `None` if it is blocked
+    fn trace_from_scene(&self, r: &Ray) -> Option<Ray> {
+        let mut r = *r;
+        let mut z = -self.front_z();
+        for (i, e) in self.elements.iter().enumerate() {
+            let eta_i = if i > 0 {
+                medium(self.elements[i - 1].ior)
+            } else {
+                1.0
+            };
+            r = self.pass(e, z, &r, eta_i, medium(e.ior))?;
+            z += e.thickness;
+        }
+        Some(r)
+    }
+
+    /// the ray through the element at `z` on the axis, from the medium `eta_i` into `eta_t`
+    fn pass(&self, e: &LensElement, z: f32, r: &Ray, eta_i: f32, eta_t: f32) -> Option<Ray> {
+        let (t, n) = if e.curvature_radius == 0.0 {
+            let t = (z - r.o.z) / r.d.z;
+            (t, Vec3::new(0.0, 0.0, 0.0))
+        } else {
+            intersect_element(e.curvature_radius, z + e.curvature_radius, r)?
+        };
+        if t.is_nan() || t <= 0.0 {
+            return None;
+        }
+        let p = r.point_at_parameter(t);
+        if p.x * p.x + p.y * p.y > e.aperture_radius * e.aperture_radius {
+            return None;
+        }
+        let d = if e.curvature_radius == 0.0 {
+            r.d
+        } else {
+            refract(r.d, n, eta_i / eta_t)?
+        };
+        Some(Ray {
+            o: p,
+            d,
+            time: r.time,
+        })
+    }
+
+    /// the z of the principal plane and the focal point of the rays parallel to the axis,
+    /// from the scene if `from_scene`, or else from the film
+    fn cardinal_points(&self, from_scene: bool) -> Result<(f32, f32), String> {
+        let x = 0.001 * self.film_diagonal();
+        let r_in = if from_scene {
+            Ray {
+                o: Pt3::new(x, 0.0, -self.front_z() - 1.0),
+                d: Vec3::new(0.0, 0.0, 1.0),
+                time: 0.0,
+            }
+        } else {
+            Ray {
+                o: Pt3::new(x, 0.0, 1.0 - self.rear_z()),
+                d: Vec3::new(0.0, 0.0, -1.0),
+                time: 0.0,
+            }
+        };
+        let r_out = if from_scene {
+            self.trace_from_scene(&r_in)
+        } else {
+            self.trace_from_film(&r_in)
+        }
+        .ok_or_else(|| String::from("the lens blocks the rays near the axis"))?;
+        let tf = -r_out.o.x / r_out.d.x;
+        let tp = (r_in.o.x - r_out.o.x) / r_out.d.x;
+        Ok((
+            r_out.point_at_parameter(tp).z,
+            r_out.point_at_parameter(tf).z,
+        ))
+    }
+
+    /// the distance from the rear element to the film, which focuses at `focus_dist`,
+    /// by the thick lens approximation
+    fn focus(&self, focus_dist: f32) -> Result<f32, String> {
+        let (pz0, fz0) = self.cardinal_points(true)?;
+        let (pz1, _) = self.cardinal_points(false)?;
+        let f = fz0 - pz0;
+        let z = -focus_dist;
+        let c = (pz1 - z - pz0) * (pz1 - z - 4.0 * f - pz0);
+        if c.is_nan() || c < 0.0 {
+            return Err(format!("the lens cannot focus at {}", focus_dist));
+        }
+        let delta = 0.5 * (pz1 - z + pz0 - c.sqrt());
+        Ok(self.rear_z() + delta)
+    }
+
+    /// the bounds of the points of the rear element, through which the light reaches
+    /// the film points between `r0` and `r1` from the center on the x axis
+    fn bound_exit_pupil(&self, r0: f32, r1: f32) -> PupilBounds {
+        const FILM_SAMPLES: usize = 4;
+        const REAR_SAMPLES: usize = 64;
+        let rear = self.elements.last().unwrap().aperture_radius;
+        let extent = 1.5 * rear;
+        let mut bounds: Option<PupilBounds> = None;
+        for i in 0..FILM_SAMPLES {
+            let x = r0 + (i as f32 + 0.5) / FILM_SAMPLES as f32 * (r1 - r0);
+            let film = Pt3::new(x, 0.0, 0.0);
+            for j in 0..REAR_SAMPLES {
+                for k in 0..REAR_SAMPLES {
+                    let px = -extent + (j as f32 + 0.5) / REAR_SAMPLES as f32 * 2.0 * extent;
+                    let py = -extent + (k as f32 + 0.5) / REAR_SAMPLES as f32 * 2.0 * extent;
+                    let r = Ray {
+                        o: film,
+                        d: Pt3::new(px, py, -self.rear_z()) - film,
+                        time: 0.0,
+                    };
+                    if self.trace_from_film(&r).is_some() {
+                        bounds = Some(match bounds {
+                            Some(b) => PupilBounds {
+                                x0: b.x0.min(px),
+                                y0: b.y0.min(py),
+                                x1: b.x1.max(px),
+                                y1: b.y1.max(py),
+                            },
+                            None => PupilBounds {
+                                x0: px,
+                                y0: py,
+                                x1: px,
+                                y1: py,
+                            },
+                        });
+                    }
+                }
+            }
+        }
+        // grow by a sample spacing, for the points missed between the samples
+        let pad = 2.0 * extent / REAR_SAMPLES as f32;
+        match bounds {
+            Some(b) => PupilBounds {
+                x0: b.x0 - pad,
+                y0: b.y0 - pad,
+                x1: b.x1 + pad,
+                y1: b.y1 + pad,
+            },
+            None => PupilBounds {
+                x0: -extent,
+                y0: -extent,
+                x1: extent,
+                y1: extent,
+            },
+        }
+    }
+
+    /// the film point of (s, t), the image is upside down through the lens
+    fn film_point(&self, s: f32, t: f32) -> Pt3 {
+        Pt3::new(
+            -(s - 0.5) * self.film_width,
+            -(t - 0.5) * self.film_height,
+            0.0,
+        )
+    }
+
+    /// the ray from the film point through the point `lens` of [-1, 1]^2 of the exit pupil,
+    /// with the bounds it is taken in
+    fn film_ray(&self, film: Pt3, lens: (f32, f32)) -> (Ray, PupilBounds) {
+        let r = (film.x * film.x + film.y * film.y).sqrt();
+        let bin =
+            ((r / (0.5 * self.film_diagonal()) * PUPIL_BINS as f32) as usize).min(PUPIL_BINS - 1);
+        let b = self.pupil[bin];
+        let px = b.x0 + 0.5 * (lens.0 + 1.0) * (b.x1 - b.x0);
+        let py = b.y0 + 0.5 * (lens.1 + 1.0) * (b.y1 - b.y0);
+        // the bounds are of the x axis, rotated to the film point
+        let (sin, cos) = if r > 0.0 {
+            (film.y / r, film.x / r)
+        } else {
+            (0.0, 1.0)
+        };
+        let rear = Pt3::new(cos * px - sin * py, sin * px + cos * py, -self.rear_z());
+        let ray = Ray {
+            o: film,
+            d: rear - film,
+            time: self.shutter.open,
+        };
+        (ray, b)
+    }
+
+    /// the ray of the lens space in the world
+    fn to_world(&self, r: &Ray) -> Ray {
+        Ray {
+            o: self.origin + r.o.x * self.u + r.o.y * self.v + r.o.z * self.w,
+            d: r.d.x * self.u + r.d.y * self.v + r.d.z * self.w,
+            time: r.time,
+        }
+    }
+}
+
+/// the index of refraction of a medium of the prescription, 0 for the air
+fn medium(ior: f32) -> f32 {
+    if ior == 0.0 {
+        1.0
+    } else {
+        ior
+    }
+}
+
+/// the hit of a spherical element of `radius` centered at `z_center` on the axis,
+/// with the normal facing the ray
+fn intersect_element(radius: f32, z_center: f32, r: &Ray) -> Option<(f32, Vec3)> {
+    let o = r.o - Pt3::new(0.0, 0.0, z_center);
+    let a = r.d.dot(r.d);
+    let b = 2.0 * r.d.dot(o);
+    let c = o.dot(o) - radius * radius;
+    let disc = b * b - 4.0 * a * c;
+    if disc < 0.0 {
+        return None;
+    }
+    let (t0, t1) = (
+        (-b - disc.sqrt()) / (2.0 * a),
+        (-b + disc.sqrt()) / (2.0 * a),
+    );
+    // the element is the half of the sphere the rays meet first along the axis
+    let closer = (r.d.z > 0.0) ^ (radius < 0.0);
+    let t = if closer { t0.min(t1) } else { t0.max(t1) };
+    if t < 0.0 {
+        return None;
+    }
+    let n = (o + t * r.d).normalize();
+    Some((t, if n.dot(r.d) > 0.0 { -n } else { n }))
+}
+
+impl Camera for RealisticCamera {
+    /// the ray through the center of the exit pupil
+    fn get_ray(&self, s: f32, t: f32) -> Ray {
+        self.get_ray_lens(s, t, (0.0, 0.0))
+    }
+
+    /// the ray through the point of the exit pupil, along the view direction if it is blocked
+    fn get_ray_lens(&self, s: f32, t: f32, lens: (f32, f32)) -> Ray {
+        let (r, _) = self.film_ray(self.film_point(s, t), lens);
+        match self.trace_from_film(&r) {
+            Some(r) => self.to_world(&r),
+            None => Ray {
+                o: self.origin,
+                d: -self.w,
+                time: r.time,
+            },
+        }
+    }
+
+    /// the light through the exit pupil, relative to the center of the film,
+    /// 0 if the lens blocks the ray
+    fn get_ray_channel(&self, s: f32, t: f32, lens: (f32, f32), _channel: usize) -> (Ray, f32) {
+        let (r, b) = self.film_ray(self.film_point(s, t), lens);
+        match self.trace_from_film(&r) {
+            Some(out) => {
+                let cos = r.d.normalize().z.abs();
+                let weight = cos.powi(4) * b.area() / self.pupil[0].area();
+                (self.to_world(&out), weight)
+            }
+            None => (
+                Ray {
+                    o: self.origin,
+                    d: -self.w,
+                    time: r.time,
+                },
+                0.0,
+            ),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "realistic"
+    }
+
+    fn eye(&self) -> Pt3 {
+        self.origin
+    }
+
+    fn view_dir(&self) -> Vec3 {
+        -self.w
+    }
+
+    fn up(&self) -> Vec3 {
+        self.v
+    }
+
+    fn shutter(&self) -> Shutter {
+        self.shutter
+    }
+
+    fn shutter_mut(&mut self) -> &mut Shutter {
+        &mut self.shutter
+    }
+
+    /// the exit pupil is sampled in its bounds
+    fn aperture(&self) -> Option<&ApertureShape> {
+        Some(&ApertureShape::Square)
+    }
+}
@@ -0,0 +1,302 @@
+use crate::camera::{ApertureSequence, Camera};
+use crate::denoise::Features;
+use crate::film::{Film, Samples};
+use crate::image::{write_image, Image};
+use crate::sampler::SampleSequence;
+use crate::scene::Scene;
+use crate::settings::Settings;
+use crate::*;
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Instant;
+
+/// the edge length of a tile, in pixels
+pub const TILE_SIZE: i32 = 16;
+
+/// a rectangle region of the image, [x0, x1) * [y0, y1)
+/// the coordinates follow the pixel order of the output image, from the top-left corner
+#[derive(Clone, Copy)]
+pub struct Tile {
+    pub x0: i32,
+    pub y0: i32,
+    pub x1: i32,
+    pub y1: i32,
+}
+
+impl Tile {
+    pub fn width(&self) -> i32 {
+        self.x1 - self.x0
+    }
+
+    pub fn height(&self) -> i32 {
+        self.y1 - self.y0
+    }
+
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x0 && x < self.x1 && y >= self.y0 && y < self.y1
+    }
+}
+
+/// split an image of `width` x `height` into tiles of `TILE_SIZE`,
+/// the tiles at the right and the bottom edges may be smaller
+pub fn split_tiles(width: i32, height: i32) -> Vec<Tile> {
+    let mut tiles = Vec::new();
+    for y0 in (0..height).step_by(TILE_SIZE as usize) {
+        for x0 in (0..width).step_by(TILE_SIZE as usize) {
+            tiles.push(Tile {
+                x0,
+                y0,
+                x1: (x0 + TILE_SIZE).min(width),
+                y1: (y0 + TILE_SIZE).min(height),
+            });
+        }
+    }
+    tiles
+}
+
+/// the number of rendering threads, one for each core
+pub fn thread_num() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// the seed of the sample sequences of pixel (i, j), the same in every pass and every run,
+/// so the passes and a resumed render go on along the same sequences
+fn pixel_seed(i: i32, j: i32, settings: &Settings) -> u64 {
+    ((settings.frame.unwrap_or(0) as u64) << 48) ^ ((j as u64) << 24) ^ i as u64
+}
+
+/// render one pass of the image on all the cores, and add the samples to the film
+/// `plan` gives the number of samples of each pixel in this pass, in the pixel order of the film
+/// `render_pixel(i, j, taken, n)` takes `n` samples of pixel (i, j) after the `taken` ones
+/// of the earlier passes, where j counts from the bottom,
+/// the same as the texture coordinate `(i / width, j / height)` passed to the camera
+pub fn render_pass<F>(film: &mut Film, plan: &[u32], render_pixel: F)
+where
+    F: Fn(i32, i32, u32, u32) -> Samples + Sync,
+{
+    let (width, height) = (film.width, film.height);
+    let taken: Vec<u32> = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| film.samples(x, y))
+        .collect();
+    let tiles = split_tiles(width, height);
+    let next_tile = AtomicUsize::new(0);
+    let film = Mutex::new(film);
+
+    thread::scope(|s| {
+        for _ in 0..thread_num() {
+            s.spawn(|| {
+                // each thread keeps taking the next tile, until all the tiles are taken
+                while let Some(tile) = tiles.get(next_tile.fetch_add(1, Ordering::Relaxed)) {
+                    let mut buf = film
+                        .lock()
+                        .unwrap()
+                        .tile(tile.x0, tile.y0, tile.x1, tile.y1);
+                    for y in tile.y0..tile.y1 {
+                        for x in tile.x0..tile.x1 {
+                            let i = (y * width + x) as usize;
+                            if plan[i] > 0 {
+                                let samples = render_pixel(x, height - 1 - y, taken[i], plan[i]);
+                                buf.add(x, y, &samples);
+                            }
+                        }
+                    }
+
+                    // add the tile to the film
+                    film.lock().unwrap().merge(&buf);
+                }
+            });
+        }
+    });
+}
+
+/// the number of samples of each pixel in the next pass, `settings.pass_spp` at most,
+/// none for the pixels with `settings.spp` samples, or under the error threshold when adaptive
+fn plan_pass(film: &Film, settings: &Settings) -> Vec<u32> {
+    let spp = settings.spp as u32;
+    let crop = settings.crop_rect();
+    let mut plan = Vec::with_capacity((film.width * film.height) as usize);
+    for y in 0..film.height {
+        for x in 0..film.width {
+            if !crop.contains(x, y) {
+                plan.push(0);
+                continue;
+            }
+            let taken = film.samples(x, y);
+            let converged = match settings.adaptive {
+                Some(threshold) => film.error(x, y) < threshold,
+                None => false,
+            };
+            plan.push(if converged {
+                0
+            } else {
+                (settings.pass_spp as u32).min(spp.saturating_sub(taken))
+            });
+        }
+    }
+    plan
+}
+
+/// develop the film into the output image, `develop` is given the film of the crop window,
+/// the image is cropped too, or pasted into a black full frame with `settings.full_frame`
+pub fn output_image<F>(film: &Film, settings: &Settings, develop: F) -> Image
+where
+    F: Fn(&Film) -> Image,
+{
+    let crop = settings.crop_rect();
+    if crop.width() == film.width && crop.height() == film.height {
+        return develop(film);
+    }
+    let image = develop(&film.crop(crop.x0, crop.y0, crop.x1, crop.y1));
+    if settings.full_frame {
+        let mut full = Image::new(film.width, film.height);
+        full.paste(&image, crop.x0, crop.y0);
+        full
+    } else {
+        image
+    }
+}
+
+/// render the scene progressively, `settings.pass_spp` samples per pixel in each pass,
+/// until every pixel has `settings.spp` samples, or has converged when adaptive
+/// with a checkpoint file, the film is saved every `settings.checkpoint_interval` seconds
+/// together with a preview of the output, and may be resumed by a later run
+pub fn render_progressive(scene: &Scene, settings: &Settings) -> io::Result<Film> {
+    render_view(scene, scene.cam.as_ref(), settings)
+}
+
+/// render the scene progressively as seen by `cam` instead of the camera of the scene
+pub fn render_view(scene: &Scene, cam: &dyn Camera, settings: &Settings) -> io::Result<Film> {
+    let now = Instant::now();
+    let hash = settings.hash(&scene.files);
+    let mut film = match &settings.checkpoint {
+        Some(path) if settings.resume => {
+            let film = Film::load_checkpoint(path, hash, settings.filter)?;
+            println!(
+                "Resumed from {}, {} samples per pixel",
+                path,
+                film.min_samples()
+            );
+            film
+        }
+        _ => Film::new(settings.width, settings.height, settings.filter),
+    };
+    let (nx, ny) = (settings.width as f32, settings.height as f32);
+    let mut last_checkpoint = Instant::now();
+
+    for pass in 1.. {
+        let plan = plan_pass(&film, settings);
+        let active = plan.iter().filter(|n| **n > 0).count();
+        if active == 0 {
+            break;
+        }
+        render_pass(&mut film, &plan, |i, j, taken, n| {
+            let mut samples = Samples::new();
+            // the sequences of all the `settings.spp` samples of the pixel, from where the last pass stopped
+            let seed = pixel_seed(i, j, settings);
+            let mut sampler =
+                SampleSequence::new(settings.pixel_sampler, 1.0, settings.spp, false, seed);
+            sampler.skip(taken as usize);
+            let shutter = cam.shutter();
+            // the lens samples, only for a camera with an aperture
+            let mut lens = cam.aperture().map(|shape| {
+                let mut lens =
+                    ApertureSequence::new(shape, settings.pixel_sampler, settings.spp, !seed);
+                lens.skip(taken as usize);
+                lens
+            });
+            // the channels take their own rays through the chromatic aberration
+            let chromatic = cam.effects().is_some_and(|e| e.chromatic != 0.0);
+            for _ in 0..n {
+                let (a, b) = sampler.sample();
+                let u = (i as f32 + a) / nx;
+                let v = (j as f32 + b) / ny;
+
+                if !cam.in_view(u, v) {
+                    samples.add(a, b, Color::new(0.0, 0.0, 0.0), Features::zero());
+                    continue;
+                }
+                let point = lens.as_mut().map_or((0.0, 0.0), |lens| lens.sample());
+                let time = shutter.time(rand::random());
+                let shade = |channel| {
+                    let (mut r, w) = cam.get_ray_channel(u, v, point, channel);
+                    r.time = time;
+                    (w * settings.integrator.shade(&r, &scene.world), r)
+                };
+                let (mut col, r) = shade(1);
+                if chromatic {
+                    col.x = shade(0).0.x;
+                    col.z = shade(2).0.z;
+                }
+                // the feature buffers cost another trace, and only the denoiser reads them
+                let features = if settings.denoise {
+                    Features::first_hit(&r, &scene.world)
+                } else {
+                    Features::zero()
+                };
+                samples.add(a, b, col, features);
+            }
+            samples
+        });
+        println!(
+            "Now rendering: pass {}, {}% pixels active, {} seconds elapsed",
+            pass,
+            (active * 100) as f32 / plan.len() as f32,
+            now.elapsed().as_secs()
+        );
+
+        if let Some(path) = &settings.checkpoint {
+            if last_checkpoint.elapsed().as_secs() >= settings.checkpoint_interval {
+                film.save_checkpoint(path, hash)?;
+                let preview = output_image(&film, settings, Film::image);
+                write_image(&settings.output, &preview, &settings.tone_mapper)?;
+                last_checkpoint = Instant::now();
+            }
+        }
+    }
+
+    // the final checkpoint, so a later run may add more samples
+    if let Some(path) = &settings.checkpoint {
+        film.save_checkpoint(path, hash)?;
+    }
+    Ok(film)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::Filter;
+    use crate::sampler::SamplerKind;
+    use std::collections::HashSet;
+
+    #[test]
+    fn passes_go_on_along_the_sequence() {
+        let (n, spp) = (16, 64);
+        let mut film = Film::new(2, 2, Filter::default());
+        let offsets = Mutex::new(vec![Vec::new(); 4]);
+        for _ in 0..2 {
+            render_pass(&mut film, &[n; 4], |i, j, taken, n| {
+                let mut sequence = SampleSequence::new(SamplerKind::Uniform, 1.0, spp, false, 7);
+                sequence.skip(taken as usize);
+                let mut samples = Samples::new();
+                for _ in 0..n {
+                    let (a, b) = sequence.sample();
+                    samples.add(a, b, Color::new(0.0, 0.0, 0.0), Features::zero());
+                    offsets.lock().unwrap()[(j * 2 + i) as usize].push((a, b));
+                }
+                samples
+            });
+        }
+        for pixel in offsets.into_inner().unwrap() {
+            // each pass takes new points of the 8x8 grid
+            let distinct: HashSet<_> = pixel
+                .iter()
+                .map(|(a, b)| (a.to_bits(), b.to_bits()))
+                .collect();
+            assert_eq!(distinct.len(), 2 * n as usize);
+        }
+        assert_eq!(film.min_samples(), 2 * n);
+    }
+}
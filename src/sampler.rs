@@ -1,7 +1,8 @@
 use rand::prelude::*;
+use rand::rngs::StdRng;
 use std::io;
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug)]
 pub enum SamplerKind {
     WhiteNoise,
     BlueNoise,
@@ -16,7 +17,40 @@ impl SamplerKind {
             2 => Ok(SamplerKind::Uniform),
             3 => Ok(SamplerKind::Jittered),
             4 => Ok(SamplerKind::BlueNoise),
-            _ => Err(io::Error::new(io::ErrorKind::Other, "unexpected integer")),
+            _ => Err(io::Error::other("unexpected integer")),
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<SamplerKind, String> {
+        match name {
+            "white" => Ok(SamplerKind::WhiteNoise),
+            "uniform" => Ok(SamplerKind::Uniform),
+            "jittered" => Ok(SamplerKind::Jittered),
+            "blue" => Ok(SamplerKind::BlueNoise),
+            _ => Err(format!("unknown sampler `{}`", name)),
+        }
+    }
+
+    /// create a sampler of this kind, on a square of `range` with `rate` samples
+    /// `disk` tells whether the samples are going to be taken in the inscribed disk
+    pub fn create(&self, range: f32, rate: i32, disk: bool) -> Box<dyn AreaSampler> {
+        self.create_seeded(range, rate, disk, random())
+    }
+
+    /// create a sampler which gives the same samples for the same `seed`
+    pub fn create_seeded(
+        &self,
+        range: f32,
+        rate: i32,
+        disk: bool,
+        seed: u64,
+    ) -> Box<dyn AreaSampler> {
+        let rng = StdRng::seed_from_u64(seed);
+        match self {
+            SamplerKind::WhiteNoise => Box::new(WhiteNoiseSampler::new(range, rate, rng)),
+            SamplerKind::Uniform => Box::new(UniformSampler::new(range, rate)),
+            SamplerKind::Jittered => Box::new(JitteredSampler::new(range, rate, rng)),
+            SamplerKind::BlueNoise => Box::new(BlueNoiseSampler::new(range, rate, disk, rng)),
         }
     }
 }
@@ -32,18 +66,14 @@ pub trait AreaSampler {
 
 /// the random sampler on a uniform distribution
 pub struct WhiteNoiseSampler {
-    rng: rand::prelude::ThreadRng,
+    rng: StdRng,
     range: f32,
     rate: i32,
 }
 
 impl WhiteNoiseSampler {
-    pub fn new(range: f32, rate: i32) -> Self {
-        WhiteNoiseSampler {
-            rng: rand::thread_rng(),
-            range: range,
-            rate: rate,
-        }
+    pub fn new(range: f32, rate: i32, rng: StdRng) -> Self {
+        WhiteNoiseSampler { rng, range, rate }
     }
 
     fn gen_point(&mut self) -> (f32, f32) {
@@ -105,9 +135,9 @@ impl UniformSampler {
         let rate = edge_rate * edge_rate;
         let interval = range / edge_rate as f32;
         UniformSampler {
-            interval: interval,
-            range: range,
-            rate: rate,
+            interval,
+            range,
+            rate,
             i: interval / 2.0,
             j: interval / 2.0,
         }
@@ -146,18 +176,19 @@ impl AreaSampler for UniformSampler {
 /// Stratified Sampling
 pub struct JitteredSampler {
     uniform_sampler: UniformSampler,
-    rng: rand::prelude::ThreadRng,
+    rng: StdRng,
     jitter_rate: f32,
 }
 
 impl JitteredSampler {
-    pub fn new(range: f32, ref_rate: i32) -> Self {
+    pub fn new(range: f32, ref_rate: i32, rng: StdRng) -> Self {
         let uniform = UniformSampler::new(range, ref_rate);
-        let jitter_rate = uniform.interval / 2.0;
+        // the samples are spread over the whole cell around its center
+        let jitter_rate = uniform.interval;
         JitteredSampler {
             uniform_sampler: uniform,
-            rng: rand::thread_rng(),
-            jitter_rate: jitter_rate,
+            rng,
+            jitter_rate,
         }
     }
 }
@@ -170,8 +201,8 @@ impl AreaSampler for JitteredSampler {
     fn sample(&mut self) -> Option<(f32, f32)> {
         self.uniform_sampler.sample().map(|(a, b)| {
             (
-                a + self.rng.gen::<f32>() * self.jitter_rate,
-                b + self.rng.gen::<f32>() * self.jitter_rate,
+                a + (self.rng.gen::<f32>() - 0.5) * self.jitter_rate,
+                b + (self.rng.gen::<f32>() - 0.5) * self.jitter_rate,
             )
         })
     }
@@ -191,18 +222,20 @@ pub struct BlueNoiseSampler {
     radius: f32,
     points: Vec<(f32, f32)>,
     is_disk: bool,
+    rng: StdRng,
 }
 
 impl BlueNoiseSampler {
-    pub fn new(range: f32, rate: i32, disk: bool) -> Self {
+    pub fn new(range: f32, rate: i32, disk: bool, rng: StdRng) -> Self {
         let radius = (range / (rate as f32).sqrt()) * 1.1;
 
         let mut ret = BlueNoiseSampler {
-            range: range,
-            rate: rate,
-            radius: radius,
+            range,
+            rate,
+            radius,
             points: Vec::new(),
             is_disk: disk,
+            rng,
         };
         // create a sequence of points of blue noise
         let points = loop {
@@ -215,7 +248,6 @@ impl BlueNoiseSampler {
     }
 
     fn generate_seq(&mut self) -> Option<Vec<(f32, f32)>> {
-        let mut rng = rand::thread_rng();
         let mut seq = Vec::new();
         for _ in 0..self.rate {
             let mut cnt = 0;
@@ -223,7 +255,10 @@ impl BlueNoiseSampler {
                 if cnt > 10 {
                     return None; // yield, for another try
                 }
-                let p = (rng.gen::<f32>() * self.range, rng.gen::<f32>() * self.range);
+                let p = (
+                    self.rng.gen::<f32>() * self.range,
+                    self.rng.gen::<f32>() * self.range,
+                );
                 if !self.conflict(&p)
                     && if self.is_disk {
                         _in_disk(self, &p)
@@ -242,7 +277,7 @@ impl BlueNoiseSampler {
 
     fn conflict(&self, p: &(f32, f32)) -> bool {
         for i in self.points.iter() {
-            if sq_dist(p, &i) < (self.radius * 2.0).powi(2) {
+            if sq_dist(p, i) < (self.radius * 2.0).powi(2) {
                 return true;
             }
         }
@@ -268,6 +303,73 @@ impl AreaSampler for BlueNoiseSampler {
     }
 }
 
+/// an endless sequence of samples on a square of `range`, or in its inscribed disk,
+/// taken from one sampler of `rate` samples after another, each in a random order,
+/// so a part of the sequence still spreads over the whole area
+/// the same `seed` gives the same sequence, which a progressive render takes pass by pass
+pub struct SampleSequence {
+    kind: SamplerKind,
+    range: f32,
+    rate: i32,
+    disk: bool,
+    rng: StdRng,
+    /// the samples left of the current sampler, taken from the back
+    points: Vec<(f32, f32)>,
+}
+
+impl SampleSequence {
+    pub fn new(kind: SamplerKind, range: f32, rate: i32, disk: bool, seed: u64) -> Self {
+        SampleSequence {
+            kind,
+            range,
+            rate,
+            disk,
+            rng: StdRng::seed_from_u64(seed),
+            points: Vec::new(),
+        }
+    }
+
+    fn refill(&mut self) {
+        let seed = self.rng.gen();
+        let mut sampler = self
+            .kind
+            .create_seeded(self.range, self.rate, self.disk, seed);
+        let next = |s: &mut Box<dyn AreaSampler>| {
+            if self.disk {
+                s.sample_in_disk()
+            } else {
+                s.sample()
+            }
+        };
+        let mut points = Vec::new();
+        while let Some(p) = next(&mut sampler) {
+            points.push(p);
+        }
+        points.shuffle(&mut self.rng);
+        self.points = points;
+    }
+
+    pub fn sample(&mut self) -> (f32, f32) {
+        // a sampler of a few samples may have none in the disk
+        while self.points.is_empty() {
+            self.refill();
+        }
+        self.points.pop().unwrap()
+    }
+
+    /// skip the first `n` samples, taken by the earlier passes
+    pub fn skip(&mut self, mut n: usize) {
+        while n > 0 {
+            while self.points.is_empty() {
+                self.refill();
+            }
+            let k = n.min(self.points.len());
+            self.points.truncate(self.points.len() - k);
+            n -= k;
+        }
+    }
+}
+
 // helper functions
 
 fn _in_disk<T: AreaSampler>(sampler: &mut T, p: &(f32, f32)) -> bool {
@@ -1,3 +1,4 @@
+use crate::animation::*;
 use crate::camera::*;
 use crate::geometry::*;
 use crate::hitable::*;
@@ -5,25 +6,124 @@ use crate::light::*;
 use crate::material::*;
 use crate::mesh::*;
 use crate::obj_loader::*;
-use crate::sampler::*;
+use crate::scene_loader::*;
+use crate::sdf::*;
+use crate::settings::*;
 use crate::shader::*;
 use crate::*;
 use cgmath::prelude::*;
-use std::rc::Rc;
+use std::error::Error;
+use std::sync::Arc;
 
 pub struct Scene {
-    pub cam: Camera,
+    pub cam: Box<dyn Camera>,
     pub world: World,
+    /// the keyframes of the camera, for the animations
+    pub track: Option<CameraTrack>,
+    /// the files the scene is loaded from, besides a scene file,
+    /// e.g. the meshes and the lens prescriptions
+    pub files: Vec<String>,
+}
+
+impl Scene {
+    /// load the scene of the settings, either a preset or a scene file
+    pub fn load(settings: &Settings) -> Result<Scene, Box<dyn Error>> {
+        let mut scene = match settings.scene.as_str() {
+            "cornell" => Scene::cornell_box(settings)?,
+            "mis" => Scene::mis_test(settings),
+            "light" => Scene::light_test(settings),
+            "blue-noise" => Scene::blue_noise_test(settings),
+            "sdf" => Scene::sdf_test(settings),
+            path => load_scene_file(path, settings)?,
+        };
+        scene.apply_camera_settings(settings)?;
+        Ok(scene)
+    }
+
+    /// move the camera to the frame of the track
+    pub fn set_frame(&mut self, frame: i32, settings: &Settings) -> Result<(), Box<dyn Error>> {
+        let track = match &self.track {
+            Some(track) => track,
+            None => return Err("the scene has no camera keyframes".into()),
+        };
+        self.cam = track.camera(frame, self.cam.as_ref(), settings.aspect());
+        self.apply_camera_settings(settings)
+    }
+
+    /// the camera options of the command line, which override the scene
+    fn apply_camera_settings(&mut self, settings: &Settings) -> Result<(), Box<dyn Error>> {
+        if let Some(projection) = settings.projection {
+            self.cam = projection.camera(self.cam.as_ref(), settings.aspect());
+        }
+        let shutter = settings.shutter(self.cam.shutter());
+        *self.cam.shutter_mut() = shutter;
+        // the lens of the command line overrides the one of the scene
+        if settings.aperture.is_some()
+            || settings.focus_dist.is_some()
+            || settings.blades.is_some()
+            || settings.aperture_mask.is_some()
+        {
+            let lens = match self.cam.lens_mut() {
+                Some(lens) => lens,
+                None => return Err(format!("the {} camera has no lens", self.cam.name()).into()),
+            };
+            if let Some(aperture) = settings.aperture {
+                lens.radius = aperture;
+            }
+            if let Some(focus_dist) = settings.focus_dist {
+                lens.focus_dist = focus_dist;
+            }
+            if let Some(blades) = settings.blades {
+                lens.shape = ApertureShape::Polygon {
+                    blades,
+                    rotation: settings.blade_rotation,
+                };
+            }
+            if let Some(path) = &settings.aperture_mask {
+                lens.shape = ApertureShape::Mask(Arc::new(ApertureMask::load(path)?));
+            }
+        }
+        if settings.distortion.is_some()
+            || settings.vignetting.is_some()
+            || settings.chromatic.is_some()
+        {
+            let effects = match self.cam.effects_mut() {
+                Some(effects) => effects,
+                None => {
+                    return Err(
+                        format!("the {} camera has no lens effects", self.cam.name()).into(),
+                    )
+                }
+            };
+            if let Some(k) = settings.distortion {
+                effects.radial = [k[0], k[1], k[2]];
+                effects.tangential = [k[3], k[4]];
+            }
+            if let Some(vignetting) = settings.vignetting {
+                effects.vignetting = vignetting;
+            }
+            if let Some(chromatic) = settings.chromatic {
+                effects.chromatic = chromatic;
+            }
+        }
+        if settings.autofocus {
+            match self.cam.autofocus(&self.world) {
+                Some(d) => println!("Autofocus at {}", d),
+                None => println!("Autofocus missed, the focus distance is kept"),
+            }
+        }
+        Ok(())
+    }
 }
 
 fn make_square(vertex: (Pt3, Pt3, Pt3, Pt3), albedo: RGBSpectrum) -> (Triangle, Triangle) {
     let t1 = Triangle {
         vertex: (vertex.0, vertex.1, vertex.2),
-        mat: Rc::new(Diffuse { albedo: albedo }),
+        mat: Arc::new(Diffuse { albedo }),
     };
     let t2 = Triangle {
         vertex: (vertex.0, vertex.2, vertex.3),
-        mat: Rc::new(Diffuse { albedo: albedo }),
+        mat: Arc::new(Diffuse { albedo }),
     };
     (t1, t2)
 }
@@ -31,11 +131,11 @@ fn make_square(vertex: (Pt3, Pt3, Pt3, Pt3), albedo: RGBSpectrum) -> (Triangle,
 fn make_mf_square(vertex: (Pt3, Pt3, Pt3, Pt3), m: Microfacet) -> (Triangle, Triangle) {
     let t1 = Triangle {
         vertex: (vertex.0, vertex.1, vertex.2),
-        mat: Rc::new(m.clone()),
+        mat: Arc::new(m.clone()),
     };
     let t2 = Triangle {
         vertex: (vertex.0, vertex.2, vertex.3),
-        mat: Rc::new(m),
+        mat: Arc::new(m),
     };
     (t1, t2)
 }
@@ -61,7 +161,7 @@ fn make_chess_board(vertex: (Pt3, Pt3, Pt3, Pt3), num: i32) -> Vec<Triangle> {
 }
 
 impl Scene {
-    pub fn cornell_box(sampler_kind: SamplerKind) -> obj::ObjResult<Scene> {
+    pub fn cornell_box(settings: &Settings) -> obj::ObjResult<Scene> {
         let mut pyramid = load_obj_file(
             String::from("./input/pyramid.obj"),
             // Dielectric {ref_idx: 1.8}
@@ -84,29 +184,6 @@ impl Scene {
         // miku.rotate(-90.0, 0.0, -35.0);
         // miku.displacement(Vec3::new(0.5, -0.97, -1.3));
 
-        let mut miku2 = load_obj_file(
-            String::from("./input/.miku2.obj"),
-            // Dielectric{ref_idx: 1.8}
-            // Metal {
-            //     albedo: Vec3::new(1.0, 0.7, 0.9),
-            // },
-            Microfacet {
-                f0: RGBSpectrum::new(0.08, 0.08, 0.08),
-                roughness: 0.3,
-                metallic: 0.1,
-                attenuation: RGBSpectrum::new(1.0, 0.7, 0.9),
-            },
-        )?;
-        miku2.transform(0.008, Vec3::new(0.6, -1.0, -1.3), -90.0, 0.0, -35.0);
-
-        let mut miku3 = load_obj_file(
-            String::from("./input/.miku3.obj"),
-            Dielectric{ref_idx: 1.8}
-            // Metal {
-            //     albedo: Vec3::new(1.0, 1.0, 1.0),
-            // },
-        )?;
-        miku3.transform(0.06, Vec3::new(-0.5, -1.0, -1.5), -90.0, 0.0, 35.0);
         let mut utah = load_obj_file(
             String::from("./input/utah.obj"),
             // Dielectric { ref_idx: 1.8 },
@@ -157,23 +234,21 @@ impl Scene {
             acc_structure: square_acc,
         };
         let s = Scene {
-            cam: Camera::new(
+            cam: Box::new(PerspectiveCamera::new(
                 // Pt3::new(0.0, 10.0, 0.0),
                 // Pt3::new(0.0, -1.0, 0.0),
                 // Vec3::new(0.0, 0.0, -1.0),
                 Pt3::new(0.0, 0.0, 2.0),
                 Pt3::new(0.0, 0.0, -1.0),
                 Vec3::new(0.0, 1.0, 0.0),
-                90.0,
-                NX as f32 / NY as f32,
-            ),
+                36.9,
+                settings.aspect(),
+            )),
             world: World {
                 objects: HitableList {
                     list: vec![
                         // Box::new(utah),
                         // Box::new(miku),
-                        // Box::new(miku2),
-                        // Box::new(miku3),
                         // Box::new(pyramid),
                         Box::new(t1),
                         Box::new(t2),
@@ -192,10 +267,10 @@ impl Scene {
                         //     radius: 0.4,
                         //     y_max: -0.5,
                         //     y_min: -1.0,
-                        //     // mat: Rc::new(Metal {
+                        //     // mat: Arc::new(Metal {
                         //     //     albedo: RGBSpectrum::new(0.9, 0.7, 0.4),
                         //     // }),
-                        //     mat: Rc::new(Microfacet {
+                        //     mat: Arc::new(Microfacet {
                         //         f0: RGBSpectrum::new(0.98, 0.98, 0.98),
                         //         roughness: 0.13,
                         //         metallic: 0.9,
@@ -205,11 +280,11 @@ impl Scene {
                         // Box::new(Sphere {
                         //     center: Vec3::new(-0.34, 0.38, -2.0),
                         //     radius: 0.25,
-                        //     // mat: Rc::new(Dielectric { ref_idx: 1.5 }),
-                        //     // mat: Rc::new(Metal {
+                        //     // mat: Arc::new(Dielectric { ref_idx: 1.5 }),
+                        //     // mat: Arc::new(Metal {
                         //     //     albedo: RGBSpectrum::new(0.4, 0.7, 0.9),
                         //     // }),
-                        //     mat: Rc::new(Microfacet {
+                        //     mat: Arc::new(Microfacet {
                         //         f0: RGBSpectrum::new(0.09, 0.09, 0.59),
                         //         roughness: 0.05,
                         //         metallic: 0.1,
@@ -219,7 +294,7 @@ impl Scene {
                         Box::new(Sphere {
                             center: Vec3::new(-0.6, -0.75, -2.5),
                             radius: 0.25,
-                            mat: Rc::new(Microfacet {
+                            mat: Arc::new(Microfacet {
                                 f0: RGBSpectrum::new(0.89, 0.89, 0.89),
                                 roughness: 0.05,
                                 metallic: 0.8,
@@ -229,7 +304,7 @@ impl Scene {
                         Box::new(Sphere {
                             center: Vec3::new(0.0, -0.75, -2.5),
                             radius: 0.25,
-                            mat: Rc::new(Microfacet {
+                            mat: Arc::new(Microfacet {
                                 f0: RGBSpectrum::new(0.89, 0.89, 0.89),
                                 roughness: 0.2,
                                 metallic: 0.8,
@@ -239,7 +314,7 @@ impl Scene {
                         Box::new(Sphere {
                             center: Vec3::new(0.6, -0.75, -2.5),
                             radius: 0.25,
-                            mat: Rc::new(Microfacet {
+                            mat: Arc::new(Microfacet {
                                 f0: RGBSpectrum::new(0.89, 0.89, 0.89),
                                 roughness: 0.5,
                                 metallic: 0.8,
@@ -249,7 +324,7 @@ impl Scene {
                         Box::new(Sphere {
                             center: Vec3::new(-0.6, -0.125, -2.5),
                             radius: 0.25,
-                            mat: Rc::new(Microfacet {
+                            mat: Arc::new(Microfacet {
                                 f0: RGBSpectrum::new(0.59, 0.59, 0.59),
                                 roughness: 0.05,
                                 metallic: 0.5,
@@ -259,7 +334,7 @@ impl Scene {
                         Box::new(Sphere {
                             center: Vec3::new(0.0, -0.125, -2.5),
                             radius: 0.25,
-                            mat: Rc::new(Microfacet {
+                            mat: Arc::new(Microfacet {
                                 f0: RGBSpectrum::new(0.59, 0.59, 0.59),
                                 roughness: 0.2,
                                 metallic: 0.5,
@@ -269,7 +344,7 @@ impl Scene {
                         Box::new(Sphere {
                             center: Vec3::new(0.6, -0.125, -2.5),
                             radius: 0.25,
-                            mat: Rc::new(Microfacet {
+                            mat: Arc::new(Microfacet {
                                 f0: RGBSpectrum::new(0.59, 0.59, 0.59),
                                 roughness: 0.5,
                                 metallic: 0.5,
@@ -279,7 +354,7 @@ impl Scene {
                         Box::new(Sphere {
                             center: Vec3::new(-0.6, 0.5, -2.5),
                             radius: 0.25,
-                            mat: Rc::new(Microfacet {
+                            mat: Arc::new(Microfacet {
                                 f0: RGBSpectrum::new(0.09, 0.09, 0.09),
                                 roughness: 0.05,
                                 metallic: 0.1,
@@ -289,7 +364,7 @@ impl Scene {
                         Box::new(Sphere {
                             center: Vec3::new(0.0, 0.5, -2.5),
                             radius: 0.25,
-                            mat: Rc::new(Microfacet {
+                            mat: Arc::new(Microfacet {
                                 f0: RGBSpectrum::new(0.09, 0.09, 0.09),
                                 roughness: 0.2,
                                 metallic: 0.1,
@@ -299,14 +374,13 @@ impl Scene {
                         Box::new(Sphere {
                             center: Vec3::new(0.6, 0.5, -2.5),
                             radius: 0.25,
-                            mat: Rc::new(Microfacet {
+                            mat: Arc::new(Microfacet {
                                 f0: RGBSpectrum::new(0.09, 0.09, 0.09),
                                 roughness: 0.5,
                                 metallic: 0.1,
                                 attenuation: RGBSpectrum::new(0.4, 0.7, 0.9),
                             }),
                         }),
-
                     ],
                 },
                 lights: LightList {
@@ -328,32 +402,42 @@ impl Scene {
                         //     Pt3::new(0.0, 1.0, -2.0),
                         //     0.4,
                         //     RGBSpectrum::new(0.9, 0.64, 0.48) * 6.0,
-                        //     sampler_kind,
+                        //     settings.light_sampler,
+                        //     settings.light_spp,
                         // )),
                         // Box::new(DiskLight::new(
                         //     Pt3::new(-0.45, 1.0, -2.25),
                         //     0.4,
                         //     RGBSpectrum::new(0.9, 0.0, 0.48) * 6.0,
-                        //     sampler_kind,
+                        //     settings.light_sampler,
+                        //     settings.light_spp,
                         // )),
                         // Box::new(DiskLight::new(
                         //     Pt3::new(0.45, 1.0, -1.75),
                         //     0.4,
                         //     RGBSpectrum::new(0.0, 0.63, 0.48) * 6.0,
-                        //     sampler_kind,
+                        //     settings.light_sampler,
+                        //     settings.light_spp,
                         // )),
                         Box::new(PolygonLight::new(
                             square_mesh,
                             RGBSpectrum::new(0.9, 0.64, 0.28) * 22.0,
+                            settings.light_spp,
                         )),
                     ],
                 },
+                max_depth: settings.max_depth,
             },
+            track: None,
+            files: vec![
+                String::from("./input/pyramid.obj"),
+                String::from("./input/utah.obj"),
+            ],
         };
         Ok(s)
     }
 
-    pub fn blue_noise_test() -> Scene {
+    pub fn blue_noise_test(settings: &Settings) -> Scene {
         let a = (
             Pt3::new(-1.0, 0.0, -1.0),
             Pt3::new(1.0, 0.0, -1.0),
@@ -370,14 +454,15 @@ impl Scene {
         chess_board_mesh.scale(5.0);
         chess_board_mesh.rotate(10.0, 30.0, 5.1);
         chess_board_mesh.displacement(Vec3::new(0.0, -0.5, -7.0));
-        let s = Scene {
-            cam: Camera::new(
+
+        Scene {
+            cam: Box::new(PerspectiveCamera::new(
                 Pt3::new(0.0, 0.0, 2.0),
                 Pt3::new(0.0, 0.0, -1.0),
                 Vec3::new(0.0, 1.0, 0.0),
-                90.0,
-                NX as f32 / NY as f32,
-            ),
+                36.9,
+                settings.aspect(),
+            )),
             world: World {
                 objects: HitableList {
                     list: vec![Box::new(chess_board_mesh)],
@@ -395,12 +480,14 @@ impl Scene {
                         // }),
                     ],
                 },
+                max_depth: settings.max_depth,
             },
-        };
-        s
+            track: None,
+            files: Vec::new(),
+        }
     }
 
-    pub fn light_test(sampler_kind: SamplerKind) -> Scene {
+    pub fn light_test(settings: &Settings) -> Scene {
         let ceiling = -0.5;
         let ground = -0.57;
         let brightness = 8.0;
@@ -412,7 +499,7 @@ impl Scene {
         );
         let (t1, t2) = make_square(sq, RGBSpectrum::new(1.0, 1.0, 1.0));
         // let dummy_mat = Dielectric { ref_idx: 0.0 };
-        let dummy_mat = Rc::new(Dielectric { ref_idx: 0.0 });
+        let dummy_mat = Arc::new(Dielectric { ref_idx: 0.0 });
 
         let triangle = vec![Triangle {
             vertex: (
@@ -502,13 +589,13 @@ impl Scene {
         };
 
         Scene {
-            cam: Camera::new(
+            cam: Box::new(PerspectiveCamera::new(
                 Pt3::new(0.0, 1.5, 3.0),
                 Pt3::new(0.0, 0.0, -1.0),
                 Vec3::new(0.0, 1.0, 0.0),
-                90.0,
-                NX as f32 / NY as f32,
-            ),
+                26.3,
+                settings.aspect(),
+            )),
             world: World {
                 objects: HitableList {
                     list: vec![Box::new(t1), Box::new(t2)],
@@ -519,45 +606,54 @@ impl Scene {
                         //     Pt3::new(0.6, ceiling, -2.0),
                         //     0.1,
                         //     RGBSpectrum::new(0.9, 0.64, 0.48) * brightness,
-                        //     sampler_kind,
+                        //     settings.light_sampler,
+                        //     settings.light_spp,
                         // )),
                         // Box::new(DiskLight::new(
                         //     Pt3::new(0.0, ceiling, -2.0),
                         //     0.1,
                         //     RGBSpectrum::new(0.9, 0.64, 0.48) * brightness,
-                        //     sampler_kind,
+                        //     settings.light_sampler,
+                        //     settings.light_spp,
                         // )),
                         // Box::new(DiskLight::new(
                         //     Pt3::new(-0.6, ceiling, -2.0),
                         //     0.1,
                         //     RGBSpectrum::new(0.9, 0.64, 0.48) * brightness,
-                        //     sampler_kind,
+                        //     settings.light_sampler,
+                        //     settings.light_spp,
                         // )),
                         Box::new(PolygonLight::new(
                             triangle_mesh,
                             RGBSpectrum::new(0.9, 0.64, 0.48) * brightness,
+                            settings.light_spp,
                         )),
                         Box::new(PolygonLight::new(
                             square_mesh,
                             RGBSpectrum::new(0.9, 0.64, 0.48) * brightness,
+                            settings.light_spp,
                         )),
                         Box::new(PolygonLight::new(
                             hexagon_mesh,
                             RGBSpectrum::new(0.9, 0.64, 0.48) * brightness,
+                            settings.light_spp,
                         )),
                     ],
                 },
+                max_depth: settings.max_depth,
             },
+            track: None,
+            files: Vec::new(),
         }
     }
 
-    pub fn mis_test(sampler_kind: SamplerKind) -> Scene {
+    pub fn mis_test(settings: &Settings) -> Scene {
         let ceiling = 2.0;
         let ground = -1.0;
         let width = 2.0;
         let front = -1.0;
         let back = -4.0;
-        let brightness = 20.0;
+        let _brightness = 20.0;
         let sq1 @ (_, _, v3, v4) = (
             Pt3::new(-width, ground, front),
             Pt3::new(width, ground, front),
@@ -642,13 +738,13 @@ impl Scene {
         let st = interval / 2.0;
 
         Scene {
-            cam: Camera::new(
+            cam: Box::new(PerspectiveCamera::new(
                 Pt3::new(0.0, 1.0, 3.0),
                 Pt3::new(0.0, 0.0, -1.0),
                 Vec3::new(0.0, 1.0, 0.0),
-                90.0,
-                NX as f32 / NY as f32,
-            ),
+                27.3,
+                settings.aspect(),
+            )),
             world: World {
                 objects: HitableList {
                     list: vec![
@@ -672,29 +768,162 @@ impl Scene {
                             Pt3::new(st - interval * 2.0, 0.5, -3.5),
                             0.02,
                             RGBSpectrum::new(0.9, 0.4, 0.6) * 150.0,
-                            sampler_kind,
+                            settings.light_sampler,
+                            settings.light_spp,
                         )),
                         Box::new(DiskLight::new(
                             Pt3::new(st - interval, 0.5, -3.5),
                             0.05,
                             RGBSpectrum::new(0.9, 0.7, 0.3) * 60.0,
-                            sampler_kind,
+                            settings.light_sampler,
+                            settings.light_spp,
                         )),
                         Box::new(DiskLight::new(
                             Pt3::new(st, 0.5, -3.5),
                             0.1,
                             RGBSpectrum::new(0.6, 0.9, 0.4) * 40.0,
-                            sampler_kind,
+                            settings.light_sampler,
+                            settings.light_spp,
                         )),
                         Box::new(DiskLight::new(
                             Pt3::new(st + interval, 0.5, -3.5),
                             0.2,
                             RGBSpectrum::new(0.4, 0.6, 0.9) * 20.0,
-                            sampler_kind,
+                            settings.light_sampler,
+                            settings.light_spp,
+                        )),
+                    ],
+                },
+                max_depth: settings.max_depth,
+            },
+            track: None,
+            files: Vec::new(),
+        }
+    }
+
+    /// the shapes of signed distance functions, with the materials of all kinds
+    pub fn sdf_test(settings: &Settings) -> Scene {
+        let ground = -0.5;
+        let sq = (
+            Pt3::new(-3.0, ground, 1.0),
+            Pt3::new(3.0, ground, 1.0),
+            Pt3::new(3.0, ground, -5.0),
+            Pt3::new(-3.0, ground, -5.0),
+        );
+        let (t1, t2) = make_square(sq, RGBSpectrum::new(0.6, 0.6, 0.6));
+
+        let metaballs = SmoothUnion {
+            shapes: vec![
+                Box::new(SdfSphere {
+                    center: Pt3::new(-1.3, -0.2, -2.0),
+                    radius: 0.25,
+                }),
+                Box::new(SdfSphere {
+                    center: Pt3::new(-0.95, 0.05, -2.1),
+                    radius: 0.2,
+                }),
+                Box::new(SdfSphere {
+                    center: Pt3::new(-1.15, 0.3, -1.8),
+                    radius: 0.15,
+                }),
+                Box::new(SdfSphere {
+                    center: Pt3::new(-0.9, -0.3, -1.7),
+                    radius: 0.18,
+                }),
+            ],
+            k: 0.25,
+        };
+        // the twist is about the y axis, the column is moved to its place afterwards
+        let column = Twist {
+            shape: Box::new(SdfBox {
+                center: Pt3::new(0.0, 0.0, 0.0),
+                half_size: Vec3::new(0.2, 0.5, 0.2),
+                rounding: 0.03,
+            }),
+            rate: 2.0,
+        };
+        let offset = Vec3::new(0.0, 0.0, -2.2);
+        let carved = Subtraction {
+            shape: Box::new(SdfSphere {
+                center: Pt3::new(1.1, -0.1, -2.0),
+                radius: 0.4,
+            }),
+            cut: Box::new(SdfBox {
+                center: Pt3::new(1.3, 0.1, -1.8),
+                half_size: Vec3::new(0.3, 0.3, 0.3),
+                rounding: 0.0,
+            }),
+        };
+        let studs = Repetition {
+            shape: Box::new(SdfSphere {
+                center: Pt3::new(0.0, ground, 0.0),
+                radius: 0.05,
+            }),
+            period: Vec3::new(0.4, 0.0, 0.4),
+        };
+
+        Scene {
+            cam: Box::new(PerspectiveCamera::new(
+                Pt3::new(0.0, 0.0, 2.0),
+                Pt3::new(0.0, 0.0, -1.0),
+                Vec3::new(0.0, 1.0, 0.0),
+                36.9,
+                settings.aspect(),
+            )),
+            world: World {
+                objects: HitableList {
+                    list: vec![
+                        Box::new(t1),
+                        Box::new(t2),
+                        Box::new(SdfObject::new(
+                            Box::new(metaballs),
+                            Arc::new(Metal {
+                                albedo: RGBSpectrum::new(0.9, 0.7, 0.4),
+                            }),
+                        )),
+                        Box::new(SdfObject {
+                            step_scale: 0.7,
+                            ..SdfObject::new(
+                                Box::new(move |p: Pt3| column.distance(p - offset)),
+                                Arc::new(Dielectric { ref_idx: 1.5 }),
+                            )
+                        }),
+                        Box::new(SdfObject::new(
+                            Box::new(carved),
+                            Arc::new(Diffuse {
+                                albedo: RGBSpectrum::new(0.8, 0.2, 0.2),
+                            }),
+                        )),
+                        Box::new(SdfObject {
+                            max_dist: 10.0,
+                            ..SdfObject::new(
+                                Box::new(studs),
+                                Arc::new(Diffuse {
+                                    albedo: RGBSpectrum::new(0.2, 0.3, 0.8),
+                                }),
+                            )
+                        }),
+                    ],
+                },
+                lights: LightList {
+                    list: vec![
+                        Box::new(PointLight {
+                            origin: Pt3::new(0.0, 1.5, -1.0),
+                            spectrum: RGBSpectrum::new(0.9, 0.8, 0.7) * 3.0,
+                        }),
+                        Box::new(DiskLight::new(
+                            Pt3::new(0.0, 2.0, -2.0),
+                            0.5,
+                            RGBSpectrum::new(0.9, 0.8, 0.7) * 6.0,
+                            settings.light_sampler,
+                            settings.light_spp,
                         )),
                     ],
                 },
+                max_depth: settings.max_depth,
             },
+            track: None,
+            files: Vec::new(),
         }
     }
 }
@@ -0,0 +1,1239 @@
+//! loader of the text scene description format
+//!
+//! a scene file is a list of statements, one per line, `#` starts a comment
+//! each statement is a keyword followed by `key value...` pairs, in any order
+//! ```text
+//! camera [perspective] lookfrom 0 0 2 lookat 0 0 -1 [vup 0 1 0] [vfov 90] [aperture f] [focus_dist f] [autofocus]
+//!     [distortion k1 k2 k3 p1 p2] [vignetting f] [chromatic f]
+//!     [blades N] [blade_rotation deg] [aperture_mask PATH]
+//! camera orthographic lookfrom 0 0 2 lookat 0 0 -1 [vup 0 1 0] height f
+//! camera equirect lookfrom 0 0 2 lookat 0 0 -1 [vup 0 1 0]
+//! camera fisheye lookfrom 0 0 2 lookat 0 0 -1 [vup 0 1 0] [fov 180] [mapping equidistant|equisolid]
+//! camera cubemap lookfrom 0 0 2 lookat 0 0 -1 [vup 0 1 0]
+//! camera realistic lookfrom 0 0 2 lookat 0 0 -1 [vup 0 1 0] lens PATH [focus_dist f]
+//!     [stop mm] [film_diagonal 35] [scale 0.001]
+//!
+//! material NAME diffuse albedo r g b
+//! material NAME metal albedo r g b
+//! material NAME dielectric ior f
+//! material NAME microfacet f0 r g b roughness f metallic f attenuation r g b
+//!
+//! sphere center x y z radius f material NAME [end_center x y z] [times t0 t1]
+//! cylinder base x y z top x y z radius f [open] material NAME
+//! cylinder center_x f center_z f y_min f y_max f radius f [open] material NAME
+//! cone base x y z top x y z base_radius f [top_radius 0] [open] material NAME
+//! disk center x y z normal x y z radius f [inner_radius 0] material NAME
+//! torus center x y z [axis 0 1 0] major_radius f minor_radius f material NAME
+//! paraboloid vertex x y z top x y z radius f [closed] material NAME
+//! triangle vertices x0 y0 z0 x1 y1 z1 x2 y2 z2 material NAME
+//! quad vertices x0 y0 z0 ... x3 y3 z3 material NAME
+//! mesh file PATH material NAME [scale f] [rotate x y z] [translate x y z]
+//!     [end_scale f] [end_rotate x y z] [end_translate x y z] [times t0 t1]
+//!
+//! object NAME SHAPE ...
+//! solid NAME sphere|cylinder|cone|torus|paraboloid|mesh ...
+//! solid NAME union|intersection|difference A B
+//! instance object NAME [scale f | scale x y z] [rotate x y z] [translate x y z]
+//!
+//! light point origin x y z spectrum r g b [intensity f]
+//! light disk origin x y z radius f spectrum r g b [intensity f]
+//! light polygon vertices x0 y0 z0 x1 y1 z1 x2 y2 z2 ... spectrum r g b [intensity f]
+//! ```
+//! keyframe frame N lookfrom x y z lookat x y z [vup 0 1 0] [vfov 90] [interpolation linear|spline]
+//!
+//! every camera accepts `shutter open close`, the interval of the motion blur
+//! the lens of a realistic camera is a prescription file, see `realistic::load_prescription`,
+//! it is focused by moving the lens, and `stop` narrows the aperture stop
+//! `vignetting 1` is the natural cos^4 falloff, `chromatic` is the fraction the red rays
+//! spread wider than the green ones, and the blue ones narrower
+//! a sphere with `end_center` moves linearly during `times`, [0, 1] by default
+//! the ends of cylinders and cones are closed by disks unless they are `open`,
+//! the second form of a cylinder stands along the y axis,
+//! a disk with `inner_radius` is an annulus, a torus is around `axis` through its center
+//! the transforms of a mesh or an instance are applied in the order they are written,
+//! `object` defines a named object by any statement of an object, which is not in the scene itself,
+//! each of its instances shares it with its own transform, and its name is not defined again,
+//! `solid` defines a named closed shape, or the combination of two solids, placed by `instance`,
+//! a solid cannot be `open`, a paraboloid should be `closed`, and a mesh should be watertight
+//! the keyframes animate the perspective camera, each with the interpolation to the next one,
+//! another kind of camera cannot have keyframes,
+//! the `camera` statement still gives the lens, the shutter and the frame rendered by default
+//! the `end_` transforms give the end state of a moving mesh, applied on the start state,
+//! a polygon light should be a convex and planar polygon
+
+use crate::animation::*;
+use crate::camera::*;
+use crate::csg::*;
+use crate::geometry::*;
+use crate::hitable::*;
+use crate::light::*;
+use crate::material::*;
+use crate::mesh::*;
+use crate::obj_loader::*;
+use crate::realistic::*;
+use crate::scene::Scene;
+use crate::settings::Settings;
+use crate::shader::*;
+use crate::*;
+use cgmath::prelude::*;
+use cgmath::{Deg, Euler, Matrix4, Quaternion};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::sync::Arc;
+
+#[derive(Debug)]
+pub enum SceneError {
+    /// failed to read the scene file
+    Io(io::Error),
+    /// an invalid statement, with the line number counted from 1
+    Syntax { line: usize, msg: String },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneError::Io(e) => write!(f, "{}", e),
+            SceneError::Syntax { line, msg } => write!(f, "line {}: {}", line, msg),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+impl From<io::Error> for SceneError {
+    fn from(e: io::Error) -> Self {
+        SceneError::Io(e)
+    }
+}
+
+type SceneResult<T> = Result<T, SceneError>;
+
+fn syntax_err<T>(line: usize, msg: String) -> SceneResult<T> {
+    Err(SceneError::Syntax { line, msg })
+}
+
+/// the keys accepted by a statement, with the number of values following each key
+/// `None` stands for a variable number of values, i.e. all the numbers up to the next key
+type KeySpec = &'static [(&'static str, Option<usize>)];
+
+const CAMERA_KEYS: KeySpec = &[
+    ("lookfrom", Some(3)),
+    ("lookat", Some(3)),
+    ("vup", Some(3)),
+    ("vfov", Some(1)),
+    ("aperture", Some(1)),
+    ("focus_dist", Some(1)),
+    ("autofocus", Some(0)),
+    ("shutter", Some(2)),
+    ("distortion", Some(5)),
+    ("vignetting", Some(1)),
+    ("chromatic", Some(1)),
+    ("blades", Some(1)),
+    ("blade_rotation", Some(1)),
+    ("aperture_mask", Some(1)),
+];
+/// the kinds of camera, which may follow the `camera` keyword
+const CAMERA_KINDS: &[&str] = &[
+    "perspective",
+    "orthographic",
+    "equirect",
+    "fisheye",
+    "cubemap",
+    "realistic",
+];
+const KEYFRAME_KEYS: KeySpec = &[
+    ("frame", Some(1)),
+    ("lookfrom", Some(3)),
+    ("lookat", Some(3)),
+    ("vup", Some(3)),
+    ("vfov", Some(1)),
+    ("interpolation", Some(1)),
+];
+const ORTHOGRAPHIC_KEYS: KeySpec = &[
+    ("lookfrom", Some(3)),
+    ("lookat", Some(3)),
+    ("vup", Some(3)),
+    ("height", Some(1)),
+    ("shutter", Some(2)),
+];
+const PANORAMA_KEYS: KeySpec = &[
+    ("lookfrom", Some(3)),
+    ("lookat", Some(3)),
+    ("vup", Some(3)),
+    ("shutter", Some(2)),
+];
+const FISHEYE_KEYS: KeySpec = &[
+    ("lookfrom", Some(3)),
+    ("lookat", Some(3)),
+    ("vup", Some(3)),
+    ("fov", Some(1)),
+    ("mapping", Some(1)),
+    ("shutter", Some(2)),
+];
+const REALISTIC_KEYS: KeySpec = &[
+    ("lookfrom", Some(3)),
+    ("lookat", Some(3)),
+    ("vup", Some(3)),
+    ("lens", Some(1)),
+    ("focus_dist", Some(1)),
+    ("stop", Some(1)),
+    ("film_diagonal", Some(1)),
+    ("scale", Some(1)),
+    ("shutter", Some(2)),
+];
+const DIFFUSE_KEYS: KeySpec = &[("albedo", Some(3))];
+const METAL_KEYS: KeySpec = &[("albedo", Some(3))];
+const DIELECTRIC_KEYS: KeySpec = &[("ior", Some(1))];
+const MICROFACET_KEYS: KeySpec = &[
+    ("f0", Some(3)),
+    ("roughness", Some(1)),
+    ("metallic", Some(1)),
+    ("attenuation", Some(3)),
+];
+const SPHERE_KEYS: KeySpec = &[
+    ("center", Some(3)),
+    ("radius", Some(1)),
+    ("material", Some(1)),
+    ("end_center", Some(3)),
+    ("times", Some(2)),
+];
+const CYLINDER_KEYS: KeySpec = &[
+    ("base", Some(3)),
+    ("top", Some(3)),
+    ("center_x", Some(1)),
+    ("center_z", Some(1)),
+    ("y_min", Some(1)),
+    ("y_max", Some(1)),
+    ("radius", Some(1)),
+    ("open", Some(0)),
+    ("material", Some(1)),
+];
+const CONE_KEYS: KeySpec = &[
+    ("base", Some(3)),
+    ("top", Some(3)),
+    ("base_radius", Some(1)),
+    ("top_radius", Some(1)),
+    ("open", Some(0)),
+    ("material", Some(1)),
+];
+const DISK_KEYS: KeySpec = &[
+    ("center", Some(3)),
+    ("normal", Some(3)),
+    ("radius", Some(1)),
+    ("inner_radius", Some(1)),
+    ("material", Some(1)),
+];
+const TORUS_KEYS: KeySpec = &[
+    ("center", Some(3)),
+    ("axis", Some(3)),
+    ("major_radius", Some(1)),
+    ("minor_radius", Some(1)),
+    ("material", Some(1)),
+];
+const PARABOLOID_KEYS: KeySpec = &[
+    ("vertex", Some(3)),
+    ("top", Some(3)),
+    ("radius", Some(1)),
+    ("closed", Some(0)),
+    ("material", Some(1)),
+];
+const TRIANGLE_KEYS: KeySpec = &[("vertices", Some(9)), ("material", Some(1))];
+const QUAD_KEYS: KeySpec = &[("vertices", Some(12)), ("material", Some(1))];
+const MESH_KEYS: KeySpec = &[
+    ("file", Some(1)),
+    ("material", Some(1)),
+    ("scale", Some(1)),
+    ("rotate", Some(3)),
+    ("translate", Some(3)),
+    ("end_scale", Some(1)),
+    ("end_rotate", Some(3)),
+    ("end_translate", Some(3)),
+    ("times", Some(2)),
+];
+const INSTANCE_KEYS: KeySpec = &[
+    ("object", Some(1)),
+    ("scale", None),
+    ("rotate", Some(3)),
+    ("translate", Some(3)),
+];
+/// the statements of the closed shapes, which may be defined by `solid`
+const SOLID_KINDS: &[&str] = &["sphere", "cylinder", "cone", "torus", "paraboloid", "mesh"];
+/// the statements of the objects, which may be defined by `object`
+const OBJECT_KINDS: &[&str] = &[
+    "sphere",
+    "cylinder",
+    "cone",
+    "disk",
+    "torus",
+    "paraboloid",
+    "triangle",
+    "quad",
+    "mesh",
+    "instance",
+];
+const POINT_LIGHT_KEYS: KeySpec = &[
+    ("origin", Some(3)),
+    ("spectrum", Some(3)),
+    ("intensity", Some(1)),
+];
+const DISK_LIGHT_KEYS: KeySpec = &[
+    ("origin", Some(3)),
+    ("radius", Some(1)),
+    ("spectrum", Some(3)),
+    ("intensity", Some(1)),
+];
+const POLYGON_LIGHT_KEYS: KeySpec = &[
+    ("vertices", None),
+    ("spectrum", Some(3)),
+    ("intensity", Some(1)),
+];
+
+/// the `key value...` pairs of a statement, in the order they are written
+struct Props<'a> {
+    line: usize,
+    values: Vec<(&'static str, Vec<&'a str>)>,
+}
+
+fn parse_f32(line: usize, s: &str) -> SceneResult<f32> {
+    s.parse::<f32>()
+        .or_else(|_| syntax_err(line, format!("expected a number, found `{}`", s)))
+}
+
+/// the aperture shape of the `blades` or the `aperture_mask` of a camera
+fn aperture_shape(props: &Props) -> SceneResult<ApertureShape> {
+    let line = props.line;
+    match (props.get("blades"), props.get("aperture_mask")) {
+        (Some(_), Some(_)) => syntax_err(
+            line,
+            String::from("`blades` and `aperture_mask` are exclusive"),
+        ),
+        (Some(_), None) => {
+            let blades = props.f32("blades")?;
+            if blades.fract() != 0.0 || blades < 3.0 {
+                return syntax_err(line, format!("{} blades are not a polygon", blades));
+            }
+            Ok(ApertureShape::Polygon {
+                blades: blades as u32,
+                rotation: props.f32_or("blade_rotation", 0.0)?,
+            })
+        }
+        (None, Some(_)) => {
+            let path = props.word("aperture_mask")?;
+            match ApertureMask::load(path) {
+                Ok(mask) => Ok(ApertureShape::Mask(Arc::new(mask))),
+                Err(e) => syntax_err(line, format!("cannot load `{}`: {}", path, e)),
+            }
+        }
+        (None, None) if props.get("blade_rotation").is_some() => {
+            syntax_err(line, String::from("`blade_rotation` expects `blades`"))
+        }
+        (None, None) => Ok(ApertureShape::Circle),
+    }
+}
+
+impl<'a> Props<'a> {
+    fn parse(line: usize, tokens: &[&'a str], spec: KeySpec) -> SceneResult<Self> {
+        let mut values: Vec<(&'static str, Vec<&'a str>)> = Vec::new();
+        let mut i = 0;
+        while i < tokens.len() {
+            let (key, arity) = match spec.iter().find(|(k, _)| *k == tokens[i]) {
+                Some(x) => *x,
+                None => return syntax_err(line, format!("unexpected `{}`", tokens[i])),
+            };
+            if values.iter().any(|(k, _)| *k == key) {
+                return syntax_err(line, format!("duplicated key `{}`", key));
+            }
+            i += 1;
+            let end = match arity {
+                Some(n) => i + n,
+                None => {
+                    let mut end = i;
+                    while end < tokens.len() && tokens[end].parse::<f32>().is_ok() {
+                        end += 1;
+                    }
+                    end
+                }
+            };
+            if end > tokens.len() {
+                return syntax_err(line, format!("`{}` expects {} values", key, end - i));
+            }
+            values.push((key, tokens[i..end].to_vec()));
+            i = end;
+        }
+        Ok(Props { line, values })
+    }
+
+    fn get(&self, key: &str) -> Option<&[&'a str]> {
+        self.values
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, v)| v.as_slice())
+    }
+
+    fn required(&self, key: &str) -> SceneResult<&[&'a str]> {
+        match self.get(key) {
+            Some(v) => Ok(v),
+            None => syntax_err(self.line, format!("missing `{}`", key)),
+        }
+    }
+
+    fn floats(&self, key: &str) -> SceneResult<Vec<f32>> {
+        self.required(key)?
+            .iter()
+            .map(|s| parse_f32(self.line, s))
+            .collect()
+    }
+
+    fn f32(&self, key: &str) -> SceneResult<f32> {
+        Ok(self.floats(key)?[0])
+    }
+
+    fn f32_or(&self, key: &str, default: f32) -> SceneResult<f32> {
+        match self.get(key) {
+            Some(_) => self.f32(key),
+            None => Ok(default),
+        }
+    }
+
+    fn vec3(&self, key: &str) -> SceneResult<Vec3> {
+        let v = self.floats(key)?;
+        Ok(Vec3::new(v[0], v[1], v[2]))
+    }
+
+    fn vec3_or(&self, key: &str, default: Vec3) -> SceneResult<Vec3> {
+        match self.get(key) {
+            Some(_) => self.vec3(key),
+            None => Ok(default),
+        }
+    }
+
+    fn pt3(&self, key: &str) -> SceneResult<Pt3> {
+        Ok(Pt3::from_vec(self.vec3(key)?))
+    }
+
+    fn positive(&self, key: &str) -> SceneResult<f32> {
+        let v = self.f32(key)?;
+        if v > 0.0 {
+            Ok(v)
+        } else {
+            syntax_err(
+                self.line,
+                format!("`{}` should be positive, found {}", key, v),
+            )
+        }
+    }
+
+    /// the two ends of the axis of a shape, which should differ
+    fn axis(&self, from: &str, to: &str) -> SceneResult<(Pt3, Pt3)> {
+        let (a, b) = (self.pt3(from)?, self.pt3(to)?);
+        if a == b {
+            return syntax_err(self.line, format!("`{}` and `{}` are the same", from, to));
+        }
+        Ok((a, b))
+    }
+
+    /// a list of points, given by a flat list of coordinates
+    fn points(&self, key: &str) -> SceneResult<Vec<Pt3>> {
+        let v = self.floats(key)?;
+        if v.len() % 3 != 0 {
+            return syntax_err(
+                self.line,
+                format!("`{}` expects a multiple of 3 values", key),
+            );
+        }
+        Ok(v.chunks(3).map(|p| Pt3::new(p[0], p[1], p[2])).collect())
+    }
+
+    fn word(&self, key: &str) -> SceneResult<&'a str> {
+        Ok(self.required(key)?[0])
+    }
+
+    /// the time interval of a motion, [0, 1] by default
+    fn times(&self) -> SceneResult<(f32, f32)> {
+        match self.get("times") {
+            Some(_) => {
+                let t = self.floats("times")?;
+                Ok((t[0], t[1]))
+            }
+            None => Ok((0.0, 1.0)),
+        }
+    }
+
+    /// the spectrum of a light, scaled by the optional intensity
+    fn spectrum(&self) -> SceneResult<RGBSpectrum> {
+        Ok(self.vec3("spectrum")? * self.f32_or("intensity", 1.0)?)
+    }
+}
+
+/// the state of the loader
+struct Loader<'a> {
+    settings: &'a Settings,
+    cam: Option<Box<dyn Camera>>,
+    /// focus the camera once all the objects are loaded
+    autofocus: bool,
+    keyframes: Vec<Keyframe>,
+    materials: HashMap<String, Arc<dyn Material>>,
+    /// the objects defined by `object` or `solid`, shared by the instances
+    definitions: HashMap<String, Arc<dyn Hitable>>,
+    solids: HashMap<String, Arc<dyn Solid>>,
+    objects: Vec<Box<dyn Hitable>>,
+    lights: Vec<Box<dyn Light>>,
+    /// the other files read by the statements
+    files: Vec<String>,
+}
+
+impl Loader<'_> {
+    fn material(&self, props: &Props) -> SceneResult<Arc<dyn Material>> {
+        let name = props.word("material")?;
+        match self.materials.get(name) {
+            Some(m) => Ok(m.clone()),
+            None => syntax_err(props.line, format!("undefined material `{}`", name)),
+        }
+    }
+
+    fn named_solid(&self, line: usize, name: &str) -> SceneResult<Arc<dyn Solid>> {
+        match self.solids.get(name) {
+            Some(s) => Ok(s.clone()),
+            None => syntax_err(line, format!("undefined solid `{}`", name)),
+        }
+    }
+
+    /// the name of an `object` or a `solid`, which is defined only once
+    fn new_definition(&self, line: usize, name: &str) -> SceneResult<()> {
+        if self.definitions.contains_key(name) {
+            syntax_err(line, format!("object `{}` is already defined", name))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// the mesh of the statement, in its start state
+    fn mesh(&mut self, props: &Props) -> SceneResult<FastMesh> {
+        let path = props.word("file")?;
+        self.files.push(String::from(path));
+        let mut mesh = match load_obj_file_shared(String::from(path), self.material(props)?) {
+            Ok(m) => m,
+            Err(e) => return syntax_err(props.line, format!("cannot load `{}`: {}", path, e)),
+        };
+        for (key, _) in props.values.iter() {
+            match *key {
+                "scale" => mesh.scale(props.f32(key)?),
+                "rotate" => {
+                    let r = props.vec3(key)?;
+                    mesh.rotate(r.x, r.y, r.z);
+                }
+                "translate" => mesh.displacement(props.vec3(key)?),
+                _ => {}
+            }
+        }
+        Ok(mesh)
+    }
+
+    /// the shape of a statement of a closed shape, which should not be open if `closed`
+    fn solid(&mut self, line: usize, tokens: &[&str], closed: bool) -> SceneResult<Box<dyn Solid>> {
+        let open_err = |kind: &str| syntax_err(line, format!("an open {} is not a solid", kind));
+        match tokens[0] {
+            "sphere" => {
+                let props = Props::parse(line, &tokens[1..], SPHERE_KEYS)?;
+                if props.get("end_center").is_some() {
+                    let (time0, time1) = props.times()?;
+                    Ok(Box::new(MovingSphere {
+                        center0: props.vec3("center")?,
+                        center1: props.vec3("end_center")?,
+                        time0,
+                        time1,
+                        radius: props.f32("radius")?,
+                        mat: self.material(&props)?,
+                    }))
+                } else {
+                    Ok(Box::new(Sphere {
+                        center: props.vec3("center")?,
+                        radius: props.f32("radius")?,
+                        mat: self.material(&props)?,
+                    }))
+                }
+            }
+            "cylinder" => {
+                let props = Props::parse(line, &tokens[1..], CYLINDER_KEYS)?;
+                let vertical = ["center_x", "center_z", "y_min", "y_max"]
+                    .iter()
+                    .any(|k| props.get(k).is_some());
+                let (base, top) = if vertical {
+                    if props.get("base").is_some() || props.get("top").is_some() {
+                        return syntax_err(
+                            line,
+                            String::from("`base` and `top` are exclusive with the vertical form"),
+                        );
+                    }
+                    let (x, z) = (props.f32("center_x")?, props.f32("center_z")?);
+                    let (y_min, y_max) = (props.f32("y_min")?, props.f32("y_max")?);
+                    if y_min == y_max {
+                        return syntax_err(line, String::from("`y_min` and `y_max` are the same"));
+                    }
+                    (Pt3::new(x, y_min, z), Pt3::new(x, y_max, z))
+                } else {
+                    props.axis("base", "top")?
+                };
+                if closed && props.get("open").is_some() {
+                    return open_err("cylinder");
+                }
+                Ok(Box::new(Cylinder::new(
+                    base,
+                    top,
+                    props.positive("radius")?,
+                    props.get("open").is_none(),
+                    self.material(&props)?,
+                )))
+            }
+            "cone" => {
+                let props = Props::parse(line, &tokens[1..], CONE_KEYS)?;
+                let (base, top) = props.axis("base", "top")?;
+                let base_radius = props.f32("base_radius")?;
+                let top_radius = props.f32_or("top_radius", 0.0)?;
+                if base_radius < 0.0 || top_radius < 0.0 || base_radius + top_radius == 0.0 {
+                    return syntax_err(
+                        line,
+                        format!("invalid radii {} and {} of a cone", base_radius, top_radius),
+                    );
+                }
+                if closed && props.get("open").is_some() {
+                    return open_err("cone");
+                }
+                Ok(Box::new(Cone::new(
+                    base,
+                    top,
+                    base_radius,
+                    top_radius,
+                    props.get("open").is_none(),
+                    self.material(&props)?,
+                )))
+            }
+            "torus" => {
+                let props = Props::parse(line, &tokens[1..], TORUS_KEYS)?;
+                let axis = props.vec3_or("axis", Vec3::new(0.0, 1.0, 0.0))?;
+                if axis.is_zero() {
+                    return syntax_err(line, String::from("the axis of a torus is zero"));
+                }
+                Ok(Box::new(Torus::new(
+                    props.pt3("center")?,
+                    axis,
+                    props.positive("major_radius")?,
+                    props.positive("minor_radius")?,
+                    self.material(&props)?,
+                )))
+            }
+            "paraboloid" => {
+                let props = Props::parse(line, &tokens[1..], PARABOLOID_KEYS)?;
+                let (vertex, top) = props.axis("vertex", "top")?;
+                if closed && props.get("closed").is_none() {
+                    return open_err("paraboloid");
+                }
+                Ok(Box::new(Paraboloid::new(
+                    vertex,
+                    top,
+                    props.positive("radius")?,
+                    props.get("closed").is_some(),
+                    self.material(&props)?,
+                )))
+            }
+            "mesh" => {
+                let props = Props::parse(line, &tokens[1..], MESH_KEYS)?;
+                if is_moving_mesh(&props) {
+                    return syntax_err(line, String::from("a moving mesh is not a solid"));
+                }
+                Ok(Box::new(self.mesh(&props)?))
+            }
+            kind => syntax_err(line, format!("`{}` is not a solid", kind)),
+        }
+    }
+
+    fn statement(&mut self, line: usize, tokens: &[&str]) -> SceneResult<()> {
+        match tokens[0] {
+            "camera" => {
+                let (kind, tokens) = match tokens.get(1) {
+                    Some(&k) if CAMERA_KINDS.contains(&k) => (k, &tokens[2..]),
+                    _ => ("perspective", &tokens[1..]),
+                };
+                let spec = match kind {
+                    "orthographic" => ORTHOGRAPHIC_KEYS,
+                    "equirect" | "cubemap" => PANORAMA_KEYS,
+                    "fisheye" => FISHEYE_KEYS,
+                    "realistic" => REALISTIC_KEYS,
+                    _ => CAMERA_KEYS,
+                };
+                let props = Props::parse(line, tokens, spec)?;
+                let (lookfrom, lookat) = (props.pt3("lookfrom")?, props.pt3("lookat")?);
+                let vup = props.vec3_or("vup", Vec3::new(0.0, 1.0, 0.0))?;
+                let aspect = self.settings.aspect();
+                let mut cam: Box<dyn Camera> = match kind {
+                    "orthographic" => Box::new(OrthographicCamera::new(
+                        lookfrom,
+                        lookat,
+                        vup,
+                        props.f32("height")?,
+                        aspect,
+                    )),
+                    "equirect" => Box::new(EquirectangularCamera::new(lookfrom, lookat, vup)),
+                    "cubemap" => Box::new(CubeMapCamera::new(lookfrom, lookat, vup)),
+                    "realistic" => {
+                        let path = props.word("lens")?;
+                        let mut lens =
+                            load_prescription(path).or_else(|msg| syntax_err(line, msg))?;
+                        self.files.push(String::from(path));
+                        lens.scale = props.f32_or("scale", lens.scale)?;
+                        lens.film_diagonal = props.f32_or("film_diagonal", lens.film_diagonal)?;
+                        if props.get("stop").is_some() {
+                            lens.stop_diameter = Some(props.f32("stop")?);
+                        }
+                        let focus_dist =
+                            props.f32_or("focus_dist", (lookat - lookfrom).magnitude())?;
+                        let cam =
+                            RealisticCamera::new(lookfrom, lookat, vup, &lens, focus_dist, aspect)
+                                .or_else(|msg| syntax_err(line, msg))?;
+                        Box::new(cam)
+                    }
+                    "fisheye" => {
+                        let mapping = match props.get("mapping") {
+                            Some(_) => FisheyeMapping::from_name(props.word("mapping")?)
+                                .or_else(|msg| syntax_err(line, msg))?,
+                            None => FisheyeMapping::Equidistant,
+                        };
+                        Box::new(FisheyeCamera::new(
+                            lookfrom,
+                            lookat,
+                            vup,
+                            props.f32_or("fov", 180.0)?,
+                            mapping,
+                            aspect,
+                        ))
+                    }
+                    _ => {
+                        let mut cam = PerspectiveCamera::new(
+                            lookfrom,
+                            lookat,
+                            vup,
+                            props.f32_or("vfov", 90.0)?,
+                            aspect,
+                        );
+                        cam.lens = ThinLens {
+                            radius: props.f32_or("aperture", 0.0)?,
+                            focus_dist: props.f32_or("focus_dist", cam.lens.focus_dist)?,
+                            shape: aperture_shape(&props)?,
+                        };
+                        if props.get("aperture_mask").is_some() {
+                            self.files.push(String::from(props.word("aperture_mask")?));
+                        }
+                        if props.get("distortion").is_some() {
+                            let k = props.floats("distortion")?;
+                            cam.effects.radial = [k[0], k[1], k[2]];
+                            cam.effects.tangential = [k[3], k[4]];
+                        }
+                        cam.effects.vignetting = props.f32_or("vignetting", 0.0)?;
+                        cam.effects.chromatic = props.f32_or("chromatic", 0.0)?;
+                        Box::new(cam)
+                    }
+                };
+                if props.get("shutter").is_some() {
+                    let t = props.floats("shutter")?;
+                    *cam.shutter_mut() = Shutter {
+                        open: t[0],
+                        close: t[1],
+                    };
+                }
+                if !self.keyframes.is_empty() {
+                    animated_camera(line, cam.as_ref())?;
+                }
+                self.autofocus = props.get("autofocus").is_some();
+                self.cam = Some(cam);
+            }
+            "keyframe" => {
+                if let Some(cam) = &self.cam {
+                    animated_camera(line, cam.as_ref())?;
+                }
+                let props = Props::parse(line, &tokens[1..], KEYFRAME_KEYS)?;
+                let frame = props.f32("frame")?;
+                if frame.fract() != 0.0 {
+                    return syntax_err(line, format!("frame {} is not an integer", frame));
+                }
+                if self.keyframes.iter().any(|k| k.frame == frame as i32) {
+                    return syntax_err(line, format!("frame {} has a keyframe already", frame));
+                }
+                let interpolation = match props.get("interpolation") {
+                    Some(_) => Interpolation::from_name(props.word("interpolation")?)
+                        .or_else(|msg| syntax_err(line, msg))?,
+                    None => Interpolation::Linear,
+                };
+                self.keyframes.push(Keyframe {
+                    frame: frame as i32,
+                    lookfrom: props.pt3("lookfrom")?,
+                    lookat: props.pt3("lookat")?,
+                    vup: props.vec3_or("vup", Vec3::new(0.0, 1.0, 0.0))?,
+                    vfov: props.f32_or("vfov", 90.0)?,
+                    interpolation,
+                });
+            }
+            "material" => {
+                if tokens.len() < 3 {
+                    return syntax_err(line, String::from("expected `material NAME TYPE ...`"));
+                }
+                let (name, kind) = (tokens[1], tokens[2]);
+                if self.materials.contains_key(name) {
+                    return syntax_err(line, format!("material `{}` is already defined", name));
+                }
+                let mat: Arc<dyn Material> = match kind {
+                    "diffuse" => {
+                        let props = Props::parse(line, &tokens[3..], DIFFUSE_KEYS)?;
+                        Arc::new(Diffuse {
+                            albedo: props.vec3("albedo")?,
+                        })
+                    }
+                    "metal" => {
+                        let props = Props::parse(line, &tokens[3..], METAL_KEYS)?;
+                        Arc::new(Metal {
+                            albedo: props.vec3("albedo")?,
+                        })
+                    }
+                    "dielectric" => {
+                        let props = Props::parse(line, &tokens[3..], DIELECTRIC_KEYS)?;
+                        Arc::new(Dielectric {
+                            ref_idx: props.f32("ior")?,
+                        })
+                    }
+                    "microfacet" => {
+                        let props = Props::parse(line, &tokens[3..], MICROFACET_KEYS)?;
+                        Arc::new(Microfacet {
+                            f0: props.vec3("f0")?,
+                            roughness: props.f32("roughness")?,
+                            metallic: props.f32("metallic")?,
+                            attenuation: props.vec3("attenuation")?,
+                        })
+                    }
+                    _ => return syntax_err(line, format!("unknown material type `{}`", kind)),
+                };
+                self.materials.insert(String::from(name), mat);
+            }
+            "sphere" | "cylinder" | "cone" => {
+                let shape = self.solid(line, tokens, false)?;
+                self.objects.push(shape);
+            }
+            "disk" => {
+                let props = Props::parse(line, &tokens[1..], DISK_KEYS)?;
+                let normal = props.vec3("normal")?;
+                let radius = props.positive("radius")?;
+                let inner_radius = props.f32_or("inner_radius", 0.0)?;
+                if normal.is_zero() {
+                    return syntax_err(line, String::from("the normal of a disk is zero"));
+                }
+                if inner_radius < 0.0 || inner_radius >= radius {
+                    return syntax_err(
+                        line,
+                        format!("invalid inner radius {} of a disk", inner_radius),
+                    );
+                }
+                self.objects.push(Box::new(Disk::new(
+                    props.pt3("center")?,
+                    normal,
+                    radius,
+                    inner_radius,
+                    self.material(&props)?,
+                )));
+            }
+            "torus" | "paraboloid" => {
+                let shape = self.solid(line, tokens, false)?;
+                self.objects.push(shape);
+            }
+            "triangle" => {
+                let props = Props::parse(line, &tokens[1..], TRIANGLE_KEYS)?;
+                let v = props.points("vertices")?;
+                self.objects.push(Box::new(Triangle {
+                    vertex: (v[0], v[1], v[2]),
+                    mat: self.material(&props)?,
+                }));
+            }
+            "quad" => {
+                let props = Props::parse(line, &tokens[1..], QUAD_KEYS)?;
+                let v = props.points("vertices")?;
+                let mat = self.material(&props)?;
+                self.objects.push(Box::new(Triangle {
+                    vertex: (v[0], v[1], v[2]),
+                    mat: mat.clone(),
+                }));
+                self.objects.push(Box::new(Triangle {
+                    vertex: (v[0], v[2], v[3]),
+                    mat,
+                }));
+            }
+            "mesh" => {
+                let props = Props::parse(line, &tokens[1..], MESH_KEYS)?;
+                let mesh = self.mesh(&props)?;
+                if !is_moving_mesh(&props) {
+                    self.objects.push(Box::new(mesh));
+                    return Ok(());
+                }
+                // the end state is transformed further from the start state
+                let mut end = FastMesh {
+                    face_list: mesh.face_list.clone(),
+                    acc_structure: BVHTree::from_face_list(&mesh.face_list),
+                };
+                for (key, _) in props.values.iter() {
+                    match *key {
+                        "end_scale" => end.scale(props.f32(key)?),
+                        "end_rotate" => {
+                            let r = props.vec3(key)?;
+                            end.rotate(r.x, r.y, r.z);
+                        }
+                        "end_translate" => end.displacement(props.vec3(key)?),
+                        _ => {}
+                    }
+                }
+                let (time0, time1) = props.times()?;
+                self.objects
+                    .push(Box::new(MotionMesh::new(mesh, end, time0, time1)));
+            }
+            "solid" => {
+                let (name, kind) = match tokens {
+                    [_, name, kind, ..] => (*name, *kind),
+                    _ => return syntax_err(line, String::from("expected `solid NAME SHAPE ...`")),
+                };
+                self.new_definition(line, name)?;
+                let solid: Arc<dyn Solid> = match CsgOp::from_name(kind) {
+                    Ok(op) => match tokens[3..] {
+                        [a, b] => Arc::new(Csg {
+                            op,
+                            a: self.named_solid(line, a)?,
+                            b: self.named_solid(line, b)?,
+                        }),
+                        _ => {
+                            return syntax_err(line, format!("expected `solid NAME {} A B`", kind))
+                        }
+                    },
+                    Err(_) if SOLID_KINDS.contains(&kind) => {
+                        Arc::from(self.solid(line, &tokens[2..], true)?)
+                    }
+                    Err(_) => return syntax_err(line, format!("`{}` is not a solid", kind)),
+                };
+                self.definitions.insert(String::from(name), solid.clone());
+                self.solids.insert(String::from(name), solid);
+            }
+            "object" => {
+                let (name, kind) = match tokens {
+                    [_, name, kind, ..] => (*name, *kind),
+                    _ => return syntax_err(line, String::from("expected `object NAME SHAPE ...`")),
+                };
+                self.new_definition(line, name)?;
+                if !OBJECT_KINDS.contains(&kind) {
+                    return syntax_err(line, format!("`{}` is not an object", kind));
+                }
+                // the shape is loaded as usual, then taken out of the scene
+                let start = self.objects.len();
+                self.statement(line, &tokens[2..])?;
+                let mut list: Vec<Box<dyn Hitable>> = self.objects.drain(start..).collect();
+                let object: Arc<dyn Hitable> = match list.len() {
+                    1 => Arc::from(list.remove(0)),
+                    _ => Arc::new(HitableList { list }),
+                };
+                self.definitions.insert(String::from(name), object);
+            }
+            "instance" => {
+                let props = Props::parse(line, &tokens[1..], INSTANCE_KEYS)?;
+                let name = props.word("object")?;
+                let object = match self.definitions.get(name) {
+                    Some(o) => o.clone(),
+                    None => return syntax_err(line, format!("undefined object `{}`", name)),
+                };
+                let mut transform = Matrix4::identity();
+                for (key, _) in props.values.iter() {
+                    let m = match *key {
+                        "scale" => match props.floats(key)?[..] {
+                            [s] => Matrix4::from_scale(s),
+                            [x, y, z] => Matrix4::from_nonuniform_scale(x, y, z),
+                            _ => {
+                                return syntax_err(
+                                    line,
+                                    String::from("`scale` expects 1 or 3 values"),
+                                )
+                            }
+                        },
+                        "rotate" => {
+                            let r = props.vec3(key)?;
+                            Matrix4::from(Quaternion::from(Euler {
+                                x: Deg(r.x),
+                                y: Deg(r.y),
+                                z: Deg(r.z),
+                            }))
+                        }
+                        "translate" => Matrix4::from_translation(props.vec3(key)?),
+                        _ => continue,
+                    };
+                    transform = m * transform;
+                }
+                match Instance::new(object, transform) {
+                    Some(instance) => self.objects.push(Box::new(instance)),
+                    None => {
+                        return syntax_err(
+                            line,
+                            String::from("the transform of the instance is not invertible"),
+                        )
+                    }
+                }
+            }
+            "light" => {
+                if tokens.len() < 2 {
+                    return syntax_err(line, String::from("expected `light TYPE ...`"));
+                }
+                let light: Box<dyn Light> = match tokens[1] {
+                    "point" => {
+                        let props = Props::parse(line, &tokens[2..], POINT_LIGHT_KEYS)?;
+                        Box::new(PointLight {
+                            origin: props.pt3("origin")?,
+                            spectrum: props.spectrum()?,
+                        })
+                    }
+                    "disk" => {
+                        let props = Props::parse(line, &tokens[2..], DISK_LIGHT_KEYS)?;
+                        Box::new(DiskLight::new(
+                            props.pt3("origin")?,
+                            props.f32("radius")?,
+                            props.spectrum()?,
+                            self.settings.light_sampler,
+                            self.settings.light_spp,
+                        ))
+                    }
+                    "polygon" => {
+                        let props = Props::parse(line, &tokens[2..], POLYGON_LIGHT_KEYS)?;
+                        Box::new(PolygonLight::new(
+                            polygon_mesh(line, &props.points("vertices")?)?,
+                            props.spectrum()?,
+                            self.settings.light_spp,
+                        ))
+                    }
+                    kind => return syntax_err(line, format!("unknown light type `{}`", kind)),
+                };
+                self.lights.push(light);
+            }
+            keyword => return syntax_err(line, format!("unknown statement `{}`", keyword)),
+        }
+        Ok(())
+    }
+}
+
+/// the keyframes only give the parameters of a perspective camera
+fn animated_camera(line: usize, cam: &dyn Camera) -> SceneResult<()> {
+    if cam.name() == "perspective" {
+        Ok(())
+    } else {
+        syntax_err(
+            line,
+            format!("the keyframes cannot animate the {} camera", cam.name()),
+        )
+    }
+}
+
+fn is_moving_mesh(props: &Props) -> bool {
+    ["end_scale", "end_rotate", "end_translate"]
+        .iter()
+        .any(|k| props.get(k).is_some())
+}
+
+/// triangulate a convex polygon into a fan, for the polygon light
+fn polygon_mesh(line: usize, v: &[Pt3]) -> SceneResult<NaiveMesh> {
+    if v.len() < 3 {
+        return syntax_err(line, String::from("a polygon needs at least 3 vertices"));
+    }
+    // the material is never used, lights are not hit as objects
+    let mat: Arc<dyn Material> = Arc::new(Diffuse { albedo: BLACK });
+    let face_list: Vec<Triangle> = (1..v.len() - 1)
+        .map(|i| Triangle {
+            vertex: (v[0], v[i], v[i + 1]),
+            mat: mat.clone(),
+        })
+        .collect();
+    let normal = face_list[0].normal();
+    if face_list.iter().any(|t| !vec_eq(&normal, &t.normal())) {
+        return syntax_err(line, String::from("the polygon is not planar"));
+    }
+    let acc = FromFaceList::from_face_list(&face_list);
+    Ok(NaiveMesh {
+        face_list,
+        acc_structure: acc,
+    })
+}
+
+/// parse the source of a scene file
+pub fn parse_scene(src: &str, settings: &Settings) -> SceneResult<Scene> {
+    let mut loader = Loader {
+        settings,
+        cam: None,
+        autofocus: false,
+        keyframes: Vec::new(),
+        materials: HashMap::new(),
+        definitions: HashMap::new(),
+        solids: HashMap::new(),
+        objects: Vec::new(),
+        lights: Vec::new(),
+        files: Vec::new(),
+    };
+    let mut line_num = 0;
+    for (i, line) in src.lines().enumerate() {
+        line_num = i + 1;
+        let line = line.split('#').next().unwrap_or("");
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if !tokens.is_empty() {
+            loader.statement(line_num, &tokens)?;
+        }
+    }
+    match loader.cam {
+        Some(mut cam) => {
+            let world = World {
+                objects: HitableList {
+                    list: loader.objects,
+                },
+                lights: LightList {
+                    list: loader.lights,
+                },
+                max_depth: settings.max_depth,
+            };
+            if loader.autofocus {
+                cam.autofocus(&world);
+            }
+            let track = if loader.keyframes.is_empty() {
+                None
+            } else {
+                Some(CameraTrack::new(loader.keyframes))
+            };
+            Ok(Scene {
+                cam,
+                world,
+                track,
+                files: loader.files,
+            })
+        }
+        None => syntax_err(line_num, String::from("missing `camera` statement")),
+    }
+}
+
+/// load a scene file
+pub fn load_scene_file(path: &str, settings: &Settings) -> SceneResult<Scene> {
+    parse_scene(&fs::read_to_string(path)?, settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// the statements after a camera and a material `white` on the lines 1 and 2
+    fn source(statements: &str) -> String {
+        format!(
+            "camera lookfrom 0 0 2 lookat 0 0 -1\nmaterial white diffuse albedo 1 1 1\n{}",
+            statements
+        )
+    }
+
+    /// the line and the message of the error of the statements
+    fn error(statements: &str) -> (usize, String) {
+        match parse_scene(&source(statements), &Settings::default()) {
+            Ok(_) => panic!("no error in {:?}", statements),
+            Err(SceneError::Syntax { line, msg }) => (line, msg),
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    fn err(line: usize, msg: &str) -> (usize, String) {
+        (line, String::from(msg))
+    }
+
+    #[test]
+    fn bad_object() {
+        assert_eq!(
+            error("object ball"),
+            err(3, "expected `object NAME SHAPE ...`")
+        );
+        assert_eq!(
+            error("object l light point origin 0 1 0 spectrum 1 1 1"),
+            err(3, "`light` is not an object")
+        );
+        // the errors of the shape are on the line of `object`
+        assert_eq!(
+            error("\nobject ball sphere center 0 0 0 radius 1 material red"),
+            err(4, "undefined material `red`")
+        );
+        let ball = "object ball sphere center 0 0 0 radius 1 material white\n";
+        assert_eq!(
+            error(&format!("{}{}", ball, ball)),
+            err(4, "object `ball` is already defined")
+        );
+        // a solid is an object too
+        let solid = "solid ball sphere center 0 0 0 radius 1 material white\n";
+        assert_eq!(
+            error(&format!("{}{}", solid, ball)),
+            err(4, "object `ball` is already defined")
+        );
+    }
+
+    #[test]
+    fn bad_solid() {
+        assert_eq!(
+            error("solid ball"),
+            err(3, "expected `solid NAME SHAPE ...`")
+        );
+        assert_eq!(
+            error("solid d disk center 0 0 0 normal 0 1 0 radius 1 material white"),
+            err(3, "`disk` is not a solid")
+        );
+        assert_eq!(
+            error("solid c cylinder base 0 0 0 top 0 1 0 radius 1 open material white"),
+            err(3, "an open cylinder is not a solid")
+        );
+        assert_eq!(
+            error("solid p paraboloid vertex 0 0 0 top 0 1 0 radius 1 material white"),
+            err(3, "an open paraboloid is not a solid")
+        );
+        let ball = "solid ball sphere center 0 0 0 radius 1 material white\n";
+        assert_eq!(
+            error(&format!("{}solid u union ball", ball)),
+            err(4, "expected `solid NAME union A B`")
+        );
+        assert_eq!(
+            error(&format!("{}solid u difference ball cube", ball)),
+            err(4, "undefined solid `cube`")
+        );
+        assert_eq!(
+            error(&format!("{}solid ball union ball ball", ball)),
+            err(4, "object `ball` is already defined")
+        );
+        assert_eq!(
+            error("object ball sphere center 0 0 0 radius 1 material white\nsolid ball sphere center 0 0 0 radius 1 material white"),
+            err(4, "object `ball` is already defined")
+        );
+        // an object is not a solid, even of a closed shape
+        assert_eq!(
+            error(
+                "object ball sphere center 0 0 0 radius 1 material white\nsolid u union ball ball"
+            ),
+            err(4, "undefined solid `ball`")
+        );
+    }
+
+    #[test]
+    fn bad_instance() {
+        assert_eq!(
+            error("instance object ball"),
+            err(3, "undefined object `ball`")
+        );
+        let ball = "object ball sphere center 0 0 0 radius 1 material white\n";
+        assert_eq!(
+            error(&format!("{}instance object ball scale 1 2", ball)),
+            err(4, "`scale` expects 1 or 3 values")
+        );
+        assert_eq!(
+            error(&format!("{}instance object ball scale 1 0 1", ball)),
+            err(4, "the transform of the instance is not invertible")
+        );
+        // the solids are instanced like the objects
+        let ball = "solid ball sphere center 0 0 0 radius 1 material white\n";
+        let scene = source(&format!("{}instance object ball", ball));
+        assert!(parse_scene(&scene, &Settings::default()).is_ok());
+    }
+}
@@ -0,0 +1,364 @@
+//! shapes given by signed distance functions, rendered by sphere tracing
+//!
+//! see: https://iquilezles.org/articles/distfunctions/
+
+use crate::geometry::Frame;
+use crate::hitable::*;
+use crate::material::*;
+use crate::ray::Ray;
+use crate::*;
+use cgmath::prelude::*;
+use std::sync::Arc;
+
+/// a signed distance function, negative inside the shape
+/// it may underestimate the distance to the surface, but should never overestimate it,
+/// or the marching passes through the surface
+pub trait Sdf: Send + Sync {
+    fn distance(&self, p: Pt3) -> f32;
+}
+
+impl<F: Fn(Pt3) -> f32 + Send + Sync> Sdf for F {
+    fn distance(&self, p: Pt3) -> f32 {
+        self(p)
+    }
+}
+
+impl Sdf for Box<dyn Sdf> {
+    fn distance(&self, p: Pt3) -> f32 {
+        self.as_ref().distance(p)
+    }
+}
+
+pub struct SdfSphere {
+    pub center: Pt3,
+    pub radius: f32,
+}
+
+impl Sdf for SdfSphere {
+    fn distance(&self, p: Pt3) -> f32 {
+        (p - self.center).magnitude() - self.radius
+    }
+}
+
+/// an axis-aligned box, with the edges rounded by `rounding`
+pub struct SdfBox {
+    pub center: Pt3,
+    pub half_size: Vec3,
+    pub rounding: f32,
+}
+
+impl Sdf for SdfBox {
+    fn distance(&self, p: Pt3) -> f32 {
+        let q = p - self.center;
+        let q = Vec3::new(q.x.abs(), q.y.abs(), q.z.abs()) - self.half_size
+            + Vec3::new(self.rounding, self.rounding, self.rounding);
+        let outside = Vec3::new(q.x.max(0.0), q.y.max(0.0), q.z.max(0.0)).magnitude();
+        outside + q.x.max(q.y).max(q.z).min(0.0) - self.rounding
+    }
+}
+
+/// a torus around the y axis
+pub struct SdfTorus {
+    pub center: Pt3,
+    pub major_radius: f32,
+    pub minor_radius: f32,
+}
+
+impl Sdf for SdfTorus {
+    fn distance(&self, p: Pt3) -> f32 {
+        let q = p - self.center;
+        let rho = (q.x * q.x + q.z * q.z).sqrt() - self.major_radius;
+        (rho * rho + q.y * q.y).sqrt() - self.minor_radius
+    }
+}
+
+/// the points within `radius` from the segment between `a` and `b`
+pub struct SdfCapsule {
+    pub a: Pt3,
+    pub b: Pt3,
+    pub radius: f32,
+}
+
+impl Sdf for SdfCapsule {
+    fn distance(&self, p: Pt3) -> f32 {
+        let (pa, ba) = (p - self.a, self.b - self.a);
+        let h = (pa.dot(ba) / ba.dot(ba)).clamp(0.0, 1.0);
+        (pa - h * ba).magnitude() - self.radius
+    }
+}
+
+/// the union of the shapes, blended within the distance `k`, which is the plain union if zero
+/// the smooth union of spheres gives metaballs
+pub struct SmoothUnion {
+    pub shapes: Vec<Box<dyn Sdf>>,
+    pub k: f32,
+}
+
+impl Sdf for SmoothUnion {
+    fn distance(&self, p: Pt3) -> f32 {
+        self.shapes
+            .iter()
+            .map(|s| s.distance(p))
+            .reduce(|a, b| {
+                if self.k <= 0.0 {
+                    return a.min(b);
+                }
+                // the polynomial smooth minimum
+                let h = (0.5 + 0.5 * (b - a) / self.k).clamp(0.0, 1.0);
+                b + (a - b) * h - self.k * h * (1.0 - h)
+            })
+            .unwrap_or(f32::INFINITY)
+    }
+}
+
+/// the shape with the other one cut out of it
+pub struct Subtraction {
+    pub shape: Box<dyn Sdf>,
+    pub cut: Box<dyn Sdf>,
+}
+
+impl Sdf for Subtraction {
+    fn distance(&self, p: Pt3) -> f32 {
+        self.shape.distance(p).max(-self.cut.distance(p))
+    }
+}
+
+/// the shape repeated infinitely, with the cells of `period` centered at the origin,
+/// it is not repeated along the axes of zero period
+/// the shape should fit in a cell
+pub struct Repetition {
+    pub shape: Box<dyn Sdf>,
+    pub period: Vec3,
+}
+
+impl Sdf for Repetition {
+    fn distance(&self, p: Pt3) -> f32 {
+        let wrap = |x: f32, c: f32| {
+            if c == 0.0 {
+                x
+            } else {
+                x - c * (x / c).round()
+            }
+        };
+        self.shape.distance(Pt3::new(
+            wrap(p.x, self.period.x),
+            wrap(p.y, self.period.y),
+            wrap(p.z, self.period.z),
+        ))
+    }
+}
+
+/// the shape twisted about the y axis by `rate` radians per unit of height
+/// the twist stretches the distances, the marching should take shorter steps,
+/// about 1 / sqrt(1 + (rate * r)^2) for the points at the distance r from the axis
+pub struct Twist {
+    pub shape: Box<dyn Sdf>,
+    pub rate: f32,
+}
+
+impl Sdf for Twist {
+    fn distance(&self, p: Pt3) -> f32 {
+        let (s, c) = (-self.rate * p.y).sin_cos();
+        self.shape
+            .distance(Pt3::new(c * p.x - s * p.z, p.y, s * p.x + c * p.z))
+    }
+}
+
+/// the offset of the finite differences of the normal
+const NORMAL_OFFSET: f32 = 1e-4;
+
+/// a shape of a signed distance function, hit by sphere tracing
+/// there are no surface parameters, `uv` is zero and `dpdu`, `dpdv` are a unit tangent frame
+pub struct SdfObject {
+    pub sdf: Box<dyn Sdf>,
+    pub mat: Arc<dyn Material>,
+    /// the fraction of the distance each step advances, below 1 for the stretched distances
+    pub step_scale: f32,
+    pub max_steps: u32,
+    /// the distance to the surface where the marching stops
+    pub epsilon: f32,
+    /// the distance the rays are marched at most, the repeated shapes never end
+    pub max_dist: f32,
+}
+
+impl SdfObject {
+    pub fn new(sdf: Box<dyn Sdf>, mat: Arc<dyn Material>) -> Self {
+        SdfObject {
+            sdf,
+            mat,
+            step_scale: 1.0,
+            max_steps: 256,
+            epsilon: 1e-4,
+            max_dist: 100.0,
+        }
+    }
+
+    /// the outward normal by the tetrahedral finite differences
+    // see: https://iquilezles.org/articles/normalsSDF/
+    fn normal(&self, p: Pt3) -> Vec3 {
+        let h = NORMAL_OFFSET;
+        [
+            Vec3::new(1.0, -1.0, -1.0),
+            Vec3::new(-1.0, -1.0, 1.0),
+            Vec3::new(-1.0, 1.0, -1.0),
+            Vec3::new(1.0, 1.0, 1.0),
+        ]
+        .iter()
+        .map(|&k| k * self.sdf.distance(p + h * k))
+        .sum::<Vec3>()
+        .normalize()
+    }
+}
+
+impl Hitable for SdfObject {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        // march in the unit of the distance, along the unit direction
+        let len = r.d.magnitude();
+        let d = r.d / len;
+        let mut s = t_min * len;
+        let s_max = (t_max * len).min(self.max_dist);
+        let start = self.sdf.distance(r.o + s * d);
+        // a ray starting on the surface leaves it first, to the side it heads for
+        let mut leaving = start.abs() < self.epsilon;
+        let side = if leaving {
+            self.normal(r.o + s * d).dot(d).signum()
+        } else {
+            start.signum()
+        };
+        for _ in 0..self.max_steps {
+            if s > s_max {
+                return None;
+            }
+            let dist = side * self.sdf.distance(r.o + s * d);
+            if leaving {
+                if dist < self.epsilon {
+                    s += self.epsilon;
+                    continue;
+                }
+                leaving = false;
+            }
+            if dist < self.epsilon {
+                let t = s / len;
+                let normal = self.normal(r.point_at_parameter(t));
+                if normal.x.is_nan() {
+                    return None;
+                }
+                // z x x is y of the frame, the normal
+                let frame = Frame::new(r.point_at_parameter(t), normal);
+                return Some(HitRecord::new(r, t, normal, &self.mat).with_uv(
+                    Vec2::new(0.0, 0.0),
+                    frame.z,
+                    frame.x,
+                ));
+            }
+            s += self.step_scale * dist;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sphere(center: Pt3) -> Box<dyn Sdf> {
+        Box::new(SdfSphere {
+            center,
+            radius: 1.0,
+        })
+    }
+
+    fn object(sdf: Box<dyn Sdf>) -> SdfObject {
+        let mat = Arc::new(Diffuse {
+            albedo: Vec3::new(0.5, 0.5, 0.5),
+        });
+        SdfObject::new(sdf, mat)
+    }
+
+    fn ray(o: Pt3, d: Vec3) -> Ray {
+        Ray { o, d, time: 0.0 }
+    }
+
+    fn assert_hit(rec: &HitRecord, t: f32, p: Pt3, normal: Vec3, front_face: bool) {
+        assert!((rec.t - t).abs() < 1e-3, "t {} != {}", rec.t, t);
+        assert!((rec.p - p).magnitude() < 1e-3, "p {:?} != {:?}", rec.p, p);
+        assert!(
+            (rec.normal - normal).magnitude() < 1e-3,
+            "normal {:?} != {:?}",
+            rec.normal,
+            normal
+        );
+        assert_eq!(rec.front_face, front_face);
+    }
+
+    #[test]
+    fn ray_from_outside() {
+        let obj = object(sphere(Pt3::new(0.0, 0.0, 0.0)));
+        let r = ray(Pt3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -2.0));
+        let rec = obj.hit(&r, T_MIN, T_MAX).unwrap();
+        // the parameter is in the unit of the direction
+        assert_hit(
+            &rec,
+            2.0,
+            Pt3::new(0.0, 0.0, 1.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            true,
+        );
+        // away from the sphere
+        let r = ray(Pt3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(obj.hit(&r, T_MIN, T_MAX).is_none());
+    }
+
+    #[test]
+    fn ray_on_surface_heading_in() {
+        // a refracted ray, which should not stop at the surface it starts from
+        let obj = object(sphere(Pt3::new(0.0, 0.0, 0.0)));
+        let r = ray(Pt3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let rec = obj.hit(&r, 0.0, T_MAX).unwrap();
+        assert_hit(
+            &rec,
+            2.0,
+            Pt3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            false,
+        );
+    }
+
+    #[test]
+    fn ray_on_surface_heading_out() {
+        let shapes = vec![
+            sphere(Pt3::new(0.0, 0.0, 0.0)),
+            sphere(Pt3::new(0.0, 0.0, 4.0)),
+        ];
+        let obj = object(Box::new(SmoothUnion { shapes, k: 0.0 }));
+        // a reflected ray, which goes on to the next sphere
+        let r = ray(Pt3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 0.0, 1.0));
+        let rec = obj.hit(&r, 0.0, T_MAX).unwrap();
+        assert_hit(
+            &rec,
+            2.0,
+            Pt3::new(0.0, 0.0, 3.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            true,
+        );
+        // nothing else out of a single sphere
+        let obj = object(sphere(Pt3::new(0.0, 0.0, 0.0)));
+        assert!(obj.hit(&r, 0.0, T_MAX).is_none());
+    }
+
+    #[test]
+    fn miss_past_max_dist() {
+        let mut obj = object(sphere(Pt3::new(0.0, 0.0, -200.0)));
+        let r = ray(Pt3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(obj.hit(&r, T_MIN, T_MAX).is_none());
+        obj.max_dist = 300.0;
+        let rec = obj.hit(&r, T_MIN, T_MAX).unwrap();
+        assert_hit(
+            &rec,
+            199.0,
+            Pt3::new(0.0, 0.0, -199.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            true,
+        );
+    }
+}
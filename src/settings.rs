@@ -0,0 +1,525 @@
+use crate::camera::{Projection, Shutter};
+use crate::filter::*;
+use crate::render::Tile;
+use crate::sampler::*;
+use crate::shader::*;
+use crate::stereo::*;
+use crate::tonemap::*;
+use std::fs;
+use std::path::Path;
+
+/// the distance between the eyes of a stereo rig, if not given
+pub const DEFAULT_INTEROCULAR: f32 = 0.065;
+
+/// a crop window, from the top-left corner of the image
+#[derive(Clone, Copy, Debug)]
+pub enum CropWindow {
+    /// [x0, x1) * [y0, y1) in pixels
+    Pixels(i32, i32, i32, i32),
+    /// [x0, x1) * [y0, y1) in [0, 1]
+    Normalized(f32, f32, f32, f32),
+}
+
+impl CropWindow {
+    /// the pixels of the window, clipped to the image
+    pub fn rect(&self, width: i32, height: i32) -> Tile {
+        let (x0, y0, x1, y1) = match *self {
+            CropWindow::Pixels(x0, y0, x1, y1) => (x0, y0, x1, y1),
+            CropWindow::Normalized(x0, y0, x1, y1) => {
+                let (w, h) = (width as f32, height as f32);
+                (
+                    (x0 * w).floor() as i32,
+                    (y0 * h).floor() as i32,
+                    (x1 * w).ceil() as i32,
+                    (y1 * h).ceil() as i32,
+                )
+            }
+        };
+        Tile {
+            x0: x0.clamp(0, width),
+            y0: y0.clamp(0, height),
+            x1: x1.clamp(0, width),
+            y1: y1.clamp(0, height),
+        }
+    }
+}
+
+/// the settings of a rendering, given by the command line
+#[derive(Clone)]
+pub struct Settings {
+    /// a preset scene name, or the path of a scene file
+    pub scene: String,
+    pub integrator: Integrator,
+    pub pixel_sampler: SamplerKind,
+    pub light_sampler: SamplerKind,
+    pub width: i32,
+    pub height: i32,
+    /// samples per pixel
+    pub spp: i32,
+    /// samples on the area lights, for each shadow ray estimation
+    pub light_spp: i32,
+    pub max_depth: i32,
+    pub output: String,
+    pub tone_mapper: ToneMapper,
+    /// samples per pixel of each progressive pass
+    pub pass_spp: i32,
+    /// the file the accumulated samples are saved to
+    pub checkpoint: Option<String>,
+    /// seconds between two checkpoints
+    pub checkpoint_interval: u64,
+    /// continue from the checkpoint, instead of starting over
+    pub resume: bool,
+    /// stop sampling a pixel once its relative error falls below the threshold
+    pub adaptive: Option<f32>,
+    /// the image of the sample counts
+    pub heatmap: Option<String>,
+    /// the pixel reconstruction filter
+    pub filter: Filter,
+    /// run the denoiser on the final image
+    pub denoise: bool,
+    /// the radius of the thin lens, overriding the scene
+    pub aperture: Option<f32>,
+    /// the distance in focus, overriding the scene
+    pub focus_dist: Option<f32>,
+    /// focus on whatever the center ray hits
+    pub autofocus: bool,
+    /// replace the camera of the scene by a panoramic one at the same place
+    pub projection: Option<Projection>,
+    /// the shutter interval, overriding the scene
+    pub shutter_open: Option<f32>,
+    pub shutter_close: Option<f32>,
+    /// the first and the last frames of an animation
+    pub frames: Option<(i32, i32)>,
+    /// the frame being rendered, set by `for_frame`
+    pub frame: Option<i32>,
+    /// render only the pixels in the window
+    pub crop: Option<CropWindow>,
+    /// write the full frame with black out of the crop window, instead of the cropped image
+    pub full_frame: bool,
+    /// render the left and the right eyes instead of the camera of the scene
+    pub stereo: Option<StereoRig>,
+    /// how the two eyes are put into the output
+    pub stereo_layout: StereoLayout,
+    /// the eye being rendered, set by `for_eye`
+    pub eye: Option<Eye>,
+    /// the number of the aperture blades, overriding the scene
+    pub blades: Option<u32>,
+    /// the rotation of the aperture blades in degrees
+    pub blade_rotation: f32,
+    /// the image of the aperture, overriding the scene
+    pub aperture_mask: Option<String>,
+    /// the lens distortion k1, k2, k3, p1, p2, overriding the scene
+    pub distortion: Option<[f32; 5]>,
+    /// the strength of the vignetting, overriding the scene
+    pub vignetting: Option<f32>,
+    /// the lateral chromatic aberration, overriding the scene
+    pub chromatic: Option<f32>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            scene: String::from("cornell"),
+            integrator: Integrator::PathMis,
+            pixel_sampler: SamplerKind::BlueNoise,
+            light_sampler: SamplerKind::BlueNoise,
+            width: 800,
+            height: 800,
+            spp: 800,
+            light_spp: 9,
+            max_depth: 40,
+            output: String::from("./output/out.png"),
+            tone_mapper: ToneMapper::default(),
+            pass_spp: 16,
+            checkpoint: None,
+            checkpoint_interval: 60,
+            resume: false,
+            adaptive: None,
+            heatmap: None,
+            filter: Filter::default(),
+            denoise: false,
+            aperture: None,
+            focus_dist: None,
+            autofocus: false,
+            projection: None,
+            shutter_open: None,
+            shutter_close: None,
+            frames: None,
+            frame: None,
+            crop: None,
+            full_frame: false,
+            stereo: None,
+            stereo_layout: StereoLayout::SideBySide,
+            eye: None,
+            blades: None,
+            blade_rotation: 0.0,
+            aperture_mask: None,
+            distortion: None,
+            vignetting: None,
+            chromatic: None,
+        }
+    }
+}
+
+pub const USAGE: &str = "usage: main [options]
+  -s, --scene <name|file>     preset scene (cornell, mis, light, blue-noise, sdf) or a scene file
+  -i, --integrator <name>     normal, whitted, path or path-mis
+      --pixel-sampler <name>  white, uniform, jittered or blue
+      --light-sampler <name>  white, uniform, jittered or blue
+  -W, --width <n>             image width
+  -H, --height <n>            image height
+  -n, --spp <n>               samples per pixel
+      --light-spp <n>         samples on the area lights
+  -d, --max-depth <n>         max depth of the ray recursion
+  -o, --output <file>         output image, .png, .ppm, .pfm or .exr
+      --exposure <ev>         exposure compensation in stops, for .png and .ppm
+      --tonemap <name>        clamp, reinhard, reinhard-ext, aces or uncharted2
+      --white <f>             the luminance mapped to white, for reinhard-ext
+      --pass-spp <n>          samples per pixel of each progressive pass
+      --checkpoint <file>     save the accumulated samples to the file
+      --checkpoint-interval <s>
+                              seconds between two checkpoints
+      --resume                continue from the checkpoint, adding up to --spp samples
+      --adaptive <error>      sample each pixel until its relative error is below the
+                              threshold, or it has --spp samples
+      --heatmap <file>        write the sample count of the pixels as an image
+      --filter <name>         box, tent, gaussian, mitchell or lanczos
+      --filter-radius <r>     the filter radius in pixels, defaults to the usual one
+      --denoise               denoise the final image, guided by the albedo, normal and depth
+      --aperture <r>          the radius of the thin lens, 0 for a pinhole
+      --focus-dist <d>        the distance in focus
+      --autofocus             focus on whatever the center of the image shows
+      --blades <n>            a polygonal aperture of n blades, for the shape of the bokeh
+      --blade-rotation <deg>  the rotation of the aperture blades
+      --aperture-mask <file>  a PNG image of the aperture, the white passes the light
+      --distortion <k1,k2,k3,p1,p2>
+                              the Brown-Conrady radial and tangential lens distortion
+      --vignetting <f>        the strength of the cos^4 vignetting, 1 for the natural one
+      --chromatic <f>         the lateral chromatic aberration, how much wider the red
+                              rays spread than the green ones, and the blue narrower
+      --shutter-open <t>      the time the shutter opens, for the motion blur
+      --shutter-close <t>     the time the shutter closes
+      --frames <a-b>          render the frames a to b of the camera keyframes,
+                              into numbered files such as out_0001.png
+      --crop <x0,y0,x1,y1>    render only the pixels [x0, x1) * [y0, y1), from the top-left
+      --crop-norm <x0,y0,x1,y1>
+                              the crop window in [0, 1] of the image size
+      --full-frame            write the full frame with black out of the crop window
+      --stereo <mode>         render the left and the right eyes, parallel or toe-in
+      --interocular <d>       the distance between the eyes, in the units of the scene
+      --convergence <d>       the distance the toe-in eyes converge at,
+                              defaults to the focus distance
+      --stereo-layout <name>  side-by-side or anaglyph
+      --projection <name>     render a panorama from the camera of the scene,
+                              equirect, fisheye, fisheye-equisolid or cubemap
+  -h, --help                  print this message";
+
+fn parse_positive(flag: &str, value: &str) -> Result<i32, String> {
+    match value.parse::<i32>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(format!(
+            "{} expects a positive integer, found `{}`",
+            flag, value
+        )),
+    }
+}
+
+fn parse_f32(flag: &str, value: &str) -> Result<f32, String> {
+    value
+        .parse::<f32>()
+        .map_err(|_| format!("{} expects a number, found `{}`", flag, value))
+}
+
+fn parse_positive_f32(flag: &str, value: &str) -> Result<f32, String> {
+    match value.parse::<f32>() {
+        Ok(x) if x > 0.0 => Ok(x),
+        _ => Err(format!(
+            "{} expects a positive number, found `{}`",
+            flag, value
+        )),
+    }
+}
+
+/// a list of `n` numbers separated by commas
+fn parse_list<T: std::str::FromStr>(flag: &str, value: &str, n: usize) -> Result<Vec<T>, String> {
+    let err = || {
+        format!(
+            "{} expects {} numbers separated by commas, found `{}`",
+            flag, n, value
+        )
+    };
+    let v: Vec<T> = value
+        .split(',')
+        .map(|s| s.trim().parse::<T>())
+        .collect::<Result<_, _>>()
+        .map_err(|_| err())?;
+    if v.len() != n {
+        return Err(err());
+    }
+    Ok(v)
+}
+
+/// a frame range `a-b`, or a single frame `a`
+fn parse_frames(value: &str) -> Result<(i32, i32), String> {
+    let err = || format!("--frames expects a range such as `1-48`, found `{}`", value);
+    let (a, b) = value.split_once('-').unwrap_or((value, value));
+    match (a.parse::<i32>(), b.parse::<i32>()) {
+        (Ok(a), Ok(b)) if a <= b => Ok((a, b)),
+        _ => Err(err()),
+    }
+}
+
+/// insert the frame number before the extension, `out.png` to `out_0001.png`
+fn numbered(path: &str, frame: i32) -> String {
+    suffixed(path, &format!("{:04}", frame))
+}
+
+/// the path with `_suffix` after the file stem
+fn suffixed(path: &str, suffix: &str) -> String {
+    let p = Path::new(path);
+    let stem = p.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+    let name = match p.extension().and_then(|s| s.to_str()) {
+        Some(ext) => format!("{}_{}.{}", stem, suffix, ext),
+        None => format!("{}_{}", stem, suffix),
+    };
+    p.with_file_name(name).to_string_lossy().into_owned()
+}
+
+impl Settings {
+    /// the aspect ratio of the image
+    pub fn aspect(&self) -> f32 {
+        self.width as f32 / self.height as f32
+    }
+
+    /// parse the command line arguments, without the program name
+    /// returns `Ok(None)` if the usage is asked
+    pub fn from_args<I: Iterator<Item = String>>(mut args: I) -> Result<Option<Self>, String> {
+        let mut settings = Settings::default();
+        let mut filter_radius = None;
+        let (mut interocular, mut convergence) = (None, None);
+        while let Some(flag) = args.next() {
+            match flag.as_str() {
+                "-h" | "--help" => return Ok(None),
+                "--resume" => {
+                    settings.resume = true;
+                    continue;
+                }
+                "--denoise" => {
+                    settings.denoise = true;
+                    continue;
+                }
+                "--full-frame" => {
+                    settings.full_frame = true;
+                    continue;
+                }
+                "--autofocus" => {
+                    settings.autofocus = true;
+                    continue;
+                }
+                _ => {}
+            }
+            let value = match args.next() {
+                Some(v) => v,
+                None => return Err(format!("{} expects a value", flag)),
+            };
+            match flag.as_str() {
+                "-s" | "--scene" => settings.scene = value,
+                "-i" | "--integrator" => settings.integrator = Integrator::from_name(&value)?,
+                "--pixel-sampler" => settings.pixel_sampler = SamplerKind::from_name(&value)?,
+                "--light-sampler" => settings.light_sampler = SamplerKind::from_name(&value)?,
+                "-W" | "--width" => settings.width = parse_positive(&flag, &value)?,
+                "-H" | "--height" => settings.height = parse_positive(&flag, &value)?,
+                "-n" | "--spp" => settings.spp = parse_positive(&flag, &value)?,
+                "--light-spp" => settings.light_spp = parse_positive(&flag, &value)?,
+                "-d" | "--max-depth" => settings.max_depth = parse_positive(&flag, &value)?,
+                "-o" | "--output" => settings.output = value,
+                "--exposure" => settings.tone_mapper.exposure = parse_f32(&flag, &value)?,
+                "--tonemap" => settings.tone_mapper.operator = Operator::from_name(&value)?,
+                "--white" => settings.tone_mapper.white = parse_positive_f32(&flag, &value)?,
+                "--pass-spp" => settings.pass_spp = parse_positive(&flag, &value)?,
+                "--checkpoint" => settings.checkpoint = Some(value),
+                "--aperture" => settings.aperture = Some(parse_f32(&flag, &value)?),
+                "--focus-dist" => settings.focus_dist = Some(parse_f32(&flag, &value)?),
+                "--blades" => settings.blades = Some(parse_positive(&flag, &value)? as u32),
+                "--blade-rotation" => settings.blade_rotation = parse_f32(&flag, &value)?,
+                "--aperture-mask" => settings.aperture_mask = Some(value),
+                "--distortion" => {
+                    let k = parse_list::<f32>(&flag, &value, 5)?;
+                    settings.distortion = Some([k[0], k[1], k[2], k[3], k[4]]);
+                }
+                "--vignetting" => settings.vignetting = Some(parse_f32(&flag, &value)?),
+                "--chromatic" => settings.chromatic = Some(parse_f32(&flag, &value)?),
+                "--shutter-open" => settings.shutter_open = Some(parse_f32(&flag, &value)?),
+                "--shutter-close" => settings.shutter_close = Some(parse_f32(&flag, &value)?),
+                "--frames" => settings.frames = Some(parse_frames(&value)?),
+                "--crop" => {
+                    let v = parse_list::<i32>(&flag, &value, 4)?;
+                    settings.crop = Some(CropWindow::Pixels(v[0], v[1], v[2], v[3]));
+                }
+                "--crop-norm" => {
+                    let v = parse_list::<f32>(&flag, &value, 4)?;
+                    settings.crop = Some(CropWindow::Normalized(v[0], v[1], v[2], v[3]));
+                }
+                "--stereo" => {
+                    settings.stereo = Some(StereoRig {
+                        mode: StereoMode::from_name(&value)?,
+                        interocular: DEFAULT_INTEROCULAR,
+                        convergence: None,
+                    })
+                }
+                "--interocular" => interocular = Some(parse_f32(&flag, &value)?),
+                "--convergence" => convergence = Some(parse_f32(&flag, &value)?),
+                "--stereo-layout" => settings.stereo_layout = StereoLayout::from_name(&value)?,
+                "--projection" => settings.projection = Some(Projection::from_name(&value)?),
+                "--adaptive" => settings.adaptive = Some(parse_f32(&flag, &value)?),
+                "--heatmap" => settings.heatmap = Some(value),
+                "--filter" => {
+                    let kind = FilterKind::from_name(&value)?;
+                    settings.filter = Filter {
+                        kind,
+                        radius: kind.default_radius(),
+                    }
+                }
+                "--filter-radius" => filter_radius = Some(parse_f32(&flag, &value)?),
+                "--checkpoint-interval" => {
+                    settings.checkpoint_interval = parse_positive(&flag, &value)? as u64
+                }
+                _ => return Err(format!("unknown option `{}`", flag)),
+            }
+        }
+        // the radius may be given before the filter
+        if let Some(r) = filter_radius {
+            if r <= 0.0 {
+                return Err(format!(
+                    "--filter-radius expects a positive number, found `{}`",
+                    r
+                ));
+            }
+            settings.filter.radius = r;
+        }
+        match settings.stereo.as_mut() {
+            Some(rig) => {
+                rig.interocular = interocular.unwrap_or(rig.interocular);
+                rig.convergence = convergence;
+                if rig.convergence.is_some_and(|d| d <= 0.0) {
+                    return Err(String::from("--convergence expects a positive distance"));
+                }
+            }
+            None if interocular.is_some() || convergence.is_some() => {
+                return Err(String::from(
+                    "--interocular and --convergence expect a --stereo rig",
+                ));
+            }
+            None => {}
+        }
+        if settings.blades.is_some_and(|n| n < 3) {
+            return Err(String::from("--blades expects at least 3 blades"));
+        }
+        if settings.blades.is_some() && settings.aperture_mask.is_some() {
+            return Err(String::from("--blades and --aperture-mask are exclusive"));
+        }
+        if let Some(crop) = settings.crop {
+            let r = crop.rect(settings.width, settings.height);
+            if r.width() <= 0 || r.height() <= 0 {
+                return Err(String::from("the crop window is empty"));
+            }
+        }
+        if settings.resume && settings.checkpoint.is_none() {
+            return Err(String::from("--resume expects a --checkpoint file"));
+        }
+        Ok(Some(settings))
+    }
+
+    /// the pixels to render, the whole image without a crop window
+    pub fn crop_rect(&self) -> Tile {
+        match self.crop {
+            Some(crop) => crop.rect(self.width, self.height),
+            None => Tile {
+                x0: 0,
+                y0: 0,
+                x1: self.width,
+                y1: self.height,
+            },
+        }
+    }
+
+    /// the settings of a frame of the animation, with the numbered files
+    pub fn for_frame(&self, frame: i32) -> Settings {
+        let mut settings = self.clone();
+        settings.frame = Some(frame);
+        settings.output = numbered(&self.output, frame);
+        settings.checkpoint = self.checkpoint.as_ref().map(|p| numbered(p, frame));
+        settings.heatmap = self.heatmap.as_ref().map(|p| numbered(p, frame));
+        settings
+    }
+
+    /// the settings of an eye of the stereo rig, with the files of the eye,
+    /// such as out_left.png
+    pub fn for_eye(&self, eye: Eye) -> Settings {
+        let mut settings = self.clone();
+        settings.eye = Some(eye);
+        settings.output = suffixed(&self.output, eye.name());
+        settings.checkpoint = self.checkpoint.as_ref().map(|p| suffixed(p, eye.name()));
+        settings.heatmap = self.heatmap.as_ref().map(|p| suffixed(p, eye.name()));
+        settings
+    }
+
+    /// the shutter of the command line over the one of the scene
+    pub fn shutter(&self, scene: Shutter) -> Shutter {
+        Shutter {
+            open: self.shutter_open.unwrap_or(scene.open),
+            close: self.shutter_close.unwrap_or(scene.close),
+        }
+    }
+
+    /// a hash of the settings which change the radiance of the samples,
+    /// a checkpoint can only be resumed with the same hash
+    /// the sample counts, the output and the tone mapping are left out,
+    /// `denoise` is in, as the feature buffers are only traced for the denoiser,
+    /// and so are the contents of the files the scene reads, `files` besides the scene file
+    pub fn hash(&self, files: &[String]) -> u64 {
+        let desc = format!(
+            "{} {:?} {:?} {:?} {} {} {} {} {:?} {:?} {:?} {} {:?} {:?} {:?} {:?} {:?} {:?} {:?} {:?} {:?} {:?} {:?} {:?} {}",
+            self.scene,
+            self.integrator,
+            self.pixel_sampler,
+            self.light_sampler,
+            self.width,
+            self.height,
+            self.light_spp,
+            self.max_depth,
+            self.filter,
+            self.aperture,
+            self.focus_dist,
+            self.autofocus,
+            self.projection,
+            self.shutter_open,
+            self.shutter_close,
+            self.frame,
+            self.stereo,
+            self.eye,
+            self.blades,
+            self.blade_rotation,
+            self.aperture_mask,
+            self.distortion,
+            self.vignetting,
+            self.chromatic,
+            self.denoise
+        );
+        let mut bytes = desc.into_bytes();
+        // the scene file, the aperture mask, the meshes and the lenses may be edited between the runs
+        let paths = [&self.scene]
+            .into_iter()
+            .chain(&self.aperture_mask)
+            .chain(files);
+        for path in paths {
+            if let Ok(contents) = fs::read(path) {
+                bytes.extend(contents);
+            }
+        }
+        // FNV-1a, which is stable between the builds, unlike `DefaultHasher`
+        bytes.iter().fold(0xcbf29ce484222325, |h, b| {
+            (h ^ *b as u64).wrapping_mul(0x100000001b3)
+        })
+    }
+}
@@ -16,37 +16,70 @@ pub fn normal_shader(r: &Ray, world: &World) -> RGBSpectrum {
 pub struct World {
     pub objects: HitableList,
     pub lights: LightList,
+    /// the max depth of the ray recursion
+    pub max_depth: i32,
+}
+
+/// the shading algorithms
+#[derive(Clone, Copy, Debug)]
+pub enum Integrator {
+    Normal,
+    Whitted,
+    Path,
+    PathMis,
+}
+
+impl Integrator {
+    pub fn from_name(name: &str) -> Result<Integrator, String> {
+        match name {
+            "normal" => Ok(Integrator::Normal),
+            "whitted" => Ok(Integrator::Whitted),
+            "path" => Ok(Integrator::Path),
+            "path-mis" => Ok(Integrator::PathMis),
+            _ => Err(format!("unknown integrator `{}`", name)),
+        }
+    }
+
+    /// the radiance along the camera ray
+    pub fn shade(&self, r: &Ray, world: &World) -> RGBSpectrum {
+        match self {
+            Integrator::Normal => normal_shader(r, world),
+            Integrator::Whitted => whitted_trace_shader(r, world, 0),
+            Integrator::Path => path_trace_shader(r, world, 0),
+            Integrator::PathMis => path_trace_shader_mis(r, world, 0),
+        }
+    }
 }
 
 /// whitted-style ray tracing shader
-pub fn whitted_trace_shader(r: &Ray, world: &mut World, depth: i32) -> RGBSpectrum {
-    if depth > 40 {
+pub fn whitted_trace_shader(r: &Ray, world: &World, depth: i32) -> RGBSpectrum {
+    if depth > world.max_depth {
         return BLACK;
     }
 
     // hit the objects and the lights, deal with the closer one
     match (world.objects.hit(r, T_MIN, T_MAX), world.lights.hit(r)) {
-        (Some(rec), lrec) => {
-            if lrec.is_none() || lrec.unwrap().1 > rec.t {
-                let recr = &rec.clone();
-                match rec.mat {
-                    Some(m) => match m.scatter(&r, recr) {
-                        // for scatter case, the result is only dependent on the scattered ray
-                        Some(scattered) => {
-                            let t = whitted_trace_shader(&scattered, world, depth + 1);
-                            mul_v(&t, &m.attenuation())
-                        }
-                        // for diffuse case, check visibility
-                        // calculate the shadow ray
-                        None => match world.lights.visible(rec.p, rec.normal, &world.objects) {
-                            Some(direct) => mul_v(&direct, &m.attenuation()),
-                            None => BLACK,
-                        },
+        (Some(rec), Some((direct, t))) if t <= rec.t => direct,
+        (Some(rec), _) => {
+            let recr = &rec.clone();
+            match rec.mat {
+                Some(m) => match m.scatter(r, recr) {
+                    // for scatter case, the result is only dependent on the scattered ray
+                    Some(scattered) => {
+                        let t = whitted_trace_shader(&scattered, world, depth + 1);
+                        mul_v(&t, &m.attenuation())
+                    }
+                    // for diffuse case, check visibility
+                    // calculate the shadow ray
+                    None => match world
+                        .lights
+                        .visible(rec.p, rec.normal, r.time, &world.objects)
+                    {
+                        Some(direct) => mul_v(&direct, &m.attenuation()),
+                        None => BLACK,
                     },
-                    None => panic!("no material"),
-                }
-            } else {
-                lrec.unwrap().0
+                },
+                None => panic!("no material"),
             }
         }
         (None, Some((direct, _))) => direct,
@@ -55,33 +88,26 @@ pub fn whitted_trace_shader(r: &Ray, world: &mut World, depth: i32) -> RGBSpectr
 }
 
 /// distrubuted ray tracing shader, performing path tracing
-pub fn path_trace_shader(r: &Ray, world: &mut World, depth: i32) -> RGBSpectrum {
-    if depth > 40 {
+pub fn path_trace_shader(r: &Ray, world: &World, depth: i32) -> RGBSpectrum {
+    if depth > world.max_depth {
         return BLACK;
     }
 
     // hit the objects and the lights, deal with the closer one
     match (world.objects.hit(r, T_MIN, T_MAX), world.lights.hit(r)) {
-        (Some(rec), lrec) => {
-            if lrec.is_none() || lrec.unwrap().1 > rec.t {
-                let recr = &rec.clone();
-                match rec.mat {
-                    Some(m) => {
-                        let scattered = match m.scatter_d(&r, recr) {
-                            // for scatter case, the result is only dependent on the scattered ray
-                            Some(scattered) => {
-                                let t = path_trace_shader(&scattered, world, depth + 1);
-                                mul_v(&t, &m.attenuation())
-                            }
-                            None => BLACK,
-                        };
-
-                        scattered
+        (Some(rec), Some((direct, t))) if t <= rec.t => direct,
+        (Some(rec), _) => {
+            let recr = &rec.clone();
+            match rec.mat {
+                Some(m) => match m.scatter_d(r, recr) {
+                    // for scatter case, the result is only dependent on the scattered ray
+                    Some(scattered) => {
+                        let t = path_trace_shader(&scattered, world, depth + 1);
+                        mul_v(&t, &m.attenuation())
                     }
-                    None => panic!("no material"),
-                }
-            } else {
-                lrec.unwrap().0
+                    None => BLACK,
+                },
+                None => panic!("no material"),
             }
         }
         (None, Some((direct, _))) => direct,
@@ -90,23 +116,20 @@ pub fn path_trace_shader(r: &Ray, world: &mut World, depth: i32) -> RGBSpectrum
 }
 
 /// with MIS sampling
-pub fn path_trace_shader_mis(r: &Ray, world: &mut World, depth: i32) -> RGBSpectrum {
-    if depth > 40 {
+pub fn path_trace_shader_mis(r: &Ray, world: &World, depth: i32) -> RGBSpectrum {
+    if depth > world.max_depth {
         return BLACK;
     }
 
     // hit the objects and the lights, deal with the closer one
     match (world.objects.hit(r, T_MIN, T_MAX), world.lights.hit(r)) {
-        (Some(rec), lrec) => {
-            if lrec.is_none() || lrec.unwrap().1 > rec.t {
-                let recr = &rec.clone();
-                match rec.mat {
-                    // HACK: see definition of `do_material`
-                    Some(m) => m.do_material(r, recr, world, depth),
-                    None => panic!("no material"),
-                }
-            } else {
-                lrec.unwrap().0
+        (Some(rec), Some((direct, t))) if t <= rec.t => direct,
+        (Some(rec), _) => {
+            let recr = &rec.clone();
+            match rec.mat {
+                // HACK: see definition of `do_material`
+                Some(m) => m.do_material(r, recr, world, depth),
+                None => panic!("no material"),
             }
         }
         (None, Some((direct, _))) => direct,
@@ -0,0 +1,203 @@
+use crate::camera::*;
+use crate::image::Image;
+use crate::ray::*;
+use crate::*;
+use cgmath::prelude::*;
+use cgmath::{Matrix3, Rad};
+
+/// how the two eyes of the rig look at the scene
+#[derive(Clone, Copy, Debug)]
+pub enum StereoMode {
+    /// both eyes look along the view direction, they converge at infinity
+    Parallel,
+    /// the eyes are rotated to converge at a distance
+    ToeIn,
+}
+
+impl StereoMode {
+    pub fn from_name(name: &str) -> Result<StereoMode, String> {
+        match name {
+            "parallel" => Ok(StereoMode::Parallel),
+            "toe-in" => Ok(StereoMode::ToeIn),
+            _ => Err(format!("unknown stereo mode `{}`", name)),
+        }
+    }
+}
+
+/// how the images of the two eyes are put into the output
+#[derive(Clone, Copy, Debug)]
+pub enum StereoLayout {
+    /// the left eye on the left half, the right eye on the right half
+    SideBySide,
+    /// red from the left eye, green and blue from the right eye
+    Anaglyph,
+}
+
+impl StereoLayout {
+    pub fn from_name(name: &str) -> Result<StereoLayout, String> {
+        match name {
+            "side-by-side" => Ok(StereoLayout::SideBySide),
+            "anaglyph" => Ok(StereoLayout::Anaglyph),
+            _ => Err(format!("unknown stereo layout `{}`", name)),
+        }
+    }
+
+    /// the output image of the two eyes, of the same size
+    pub fn combine(&self, left: &Image, right: &Image) -> Image {
+        match self {
+            StereoLayout::SideBySide => {
+                let mut image = Image::new(left.width + right.width, left.height);
+                image.paste(left, 0, 0);
+                image.paste(right, left.width, 0);
+                image
+            }
+            StereoLayout::Anaglyph => {
+                let mut image = Image::new(left.width, left.height);
+                for (p, (l, r)) in image
+                    .pixels
+                    .iter_mut()
+                    .zip(left.pixels.iter().zip(right.pixels.iter()))
+                {
+                    *p = Color::new(l.x, r.y, r.z);
+                }
+                image
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Eye {
+    Left,
+    Right,
+}
+
+impl Eye {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Eye::Left => "left",
+            Eye::Right => "right",
+        }
+    }
+}
+
+/// a pair of cameras on both sides of the camera of the scene
+#[derive(Clone, Copy, Debug)]
+pub struct StereoRig {
+    pub mode: StereoMode,
+    /// the distance between the eyes, in the units of the scene
+    pub interocular: f32,
+    /// the distance the toe-in eyes converge at,
+    /// the focus distance of the camera if it is not given
+    pub convergence: Option<f32>,
+}
+
+impl StereoRig {
+    /// the camera of the eye, `cam` is in the middle of the eyes
+    pub fn eye<'a>(&self, eye: Eye, cam: &'a dyn Camera) -> Result<StereoEye<'a>, String> {
+        // the right direction of the basis of the camera
+        let right = cam.view_dir().cross(cam.up()).normalize();
+        let side = match eye {
+            Eye::Left => -1.0,
+            Eye::Right => 1.0,
+        };
+        let half = 0.5 * self.interocular;
+        let angle = match self.mode {
+            StereoMode::Parallel => 0.0,
+            StereoMode::ToeIn => {
+                let convergence = self
+                    .convergence
+                    .or_else(|| cam.lens().map(|l| l.focus_dist))
+                    .ok_or_else(|| {
+                        format!(
+                            "the {} camera has no focus distance, the toe-in rig needs --convergence",
+                            cam.name()
+                        )
+                    })?;
+                // turn towards the middle, clockwise about `up` for the left eye
+                side * (half / convergence).atan()
+            }
+        };
+        Ok(StereoEye {
+            cam,
+            offset: side * half * right,
+            rotation: Matrix3::from_axis_angle(cam.up(), Rad(angle)),
+            shutter: cam.shutter(),
+        })
+    }
+}
+
+/// the camera of an eye, the rays of the camera in the middle,
+/// rotated about its eye and moved sideways
+pub struct StereoEye<'a> {
+    cam: &'a dyn Camera,
+    offset: Vec3,
+    rotation: Matrix3<f32>,
+    pub shutter: Shutter,
+}
+
+impl StereoEye<'_> {
+    fn transform(&self, r: Ray) -> Ray {
+        let eye = self.cam.eye();
+        Ray {
+            o: eye + self.offset + self.rotation * (r.o - eye),
+            d: self.rotation * r.d,
+            time: r.time,
+        }
+    }
+}
+
+impl Camera for StereoEye<'_> {
+    fn get_ray(&self, s: f32, t: f32) -> Ray {
+        self.transform(self.cam.get_ray(s, t))
+    }
+
+    fn get_ray_lens(&self, s: f32, t: f32, lens: (f32, f32)) -> Ray {
+        self.transform(self.cam.get_ray_lens(s, t, lens))
+    }
+
+    fn name(&self) -> &'static str {
+        self.cam.name()
+    }
+
+    fn eye(&self) -> Pt3 {
+        self.cam.eye() + self.offset
+    }
+
+    fn view_dir(&self) -> Vec3 {
+        self.rotation * self.cam.view_dir()
+    }
+
+    fn up(&self) -> Vec3 {
+        self.cam.up()
+    }
+
+    fn shutter(&self) -> Shutter {
+        self.shutter
+    }
+
+    fn shutter_mut(&mut self) -> &mut Shutter {
+        &mut self.shutter
+    }
+
+    fn in_view(&self, s: f32, t: f32) -> bool {
+        self.cam.in_view(s, t)
+    }
+
+    fn lens(&self) -> Option<&ThinLens> {
+        self.cam.lens()
+    }
+
+    fn effects(&self) -> Option<&LensEffects> {
+        self.cam.effects()
+    }
+
+    fn aperture(&self) -> Option<&ApertureShape> {
+        self.cam.aperture()
+    }
+
+    fn get_ray_channel(&self, s: f32, t: f32, lens: (f32, f32), channel: usize) -> (Ray, f32) {
+        let (r, w) = self.cam.get_ray_channel(s, t, lens, channel);
+        (self.transform(r), w)
+    }
+}
@@ -0,0 +1,123 @@
+use crate::image::Image;
+use crate::*;
+
+/// the tone mapping operators, from linear radiance into [0, 1]
+#[derive(Clone, Copy)]
+pub enum Operator {
+    Clamp,
+    /// reinhard on the luminance
+    Reinhard,
+    /// reinhard on the luminance, where the white point maps to 1
+    ExtendedReinhard,
+    /// the ACES filmic curve, fitted by Krzysztof Narkowicz
+    Aces,
+    /// the filmic curve of Uncharted 2, by John Hable
+    Uncharted2,
+}
+
+impl Operator {
+    pub fn from_name(name: &str) -> Result<Operator, String> {
+        match name {
+            "clamp" => Ok(Operator::Clamp),
+            "reinhard" => Ok(Operator::Reinhard),
+            "reinhard-ext" => Ok(Operator::ExtendedReinhard),
+            "aces" => Ok(Operator::Aces),
+            "uncharted2" => Ok(Operator::Uncharted2),
+            _ => Err(format!("unknown tone mapping operator `{}`", name)),
+        }
+    }
+}
+
+/// the post stage, turning the accumulated radiance into display values
+#[derive(Clone, Copy)]
+pub struct ToneMapper {
+    /// exposure compensation in stops, the radiance is scaled by 2^exposure
+    pub exposure: f32,
+    pub operator: Operator,
+    /// the smallest luminance mapped to pure white, for the extended reinhard
+    pub white: f32,
+}
+
+impl Default for ToneMapper {
+    fn default() -> Self {
+        ToneMapper {
+            exposure: 0.0,
+            operator: Operator::Clamp,
+            white: 4.0,
+        }
+    }
+}
+
+// see: https://en.wikipedia.org/wiki/Relative_luminance
+pub fn luminance(c: Color) -> f32 {
+    0.2126 * c.x + 0.7152 * c.y + 0.0722 * c.z
+}
+
+/// scale the color to the mapped luminance, keeping the hue
+fn map_luminance(c: Color, f: impl Fn(f32) -> f32) -> Color {
+    let l = luminance(c);
+    if l > 0.0 {
+        c * (f(l) / l)
+    } else {
+        c
+    }
+}
+
+fn map_channels(c: Color, f: impl Fn(f32) -> f32) -> Color {
+    Color::new(f(c.x), f(c.y), f(c.z))
+}
+
+// see: https://knarkowicz.wordpress.com/2016/01/06/aces-filmic-tone-mapping-curve/
+fn aces(x: f32) -> f32 {
+    (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)
+}
+
+// see: http://filmicworlds.com/blog/filmic-tonemapping-operators/
+fn uncharted2(x: f32) -> f32 {
+    let (a, b, c, d, e, f) = (0.15, 0.50, 0.10, 0.20, 0.02, 0.30);
+    ((x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f)) - e / f
+}
+
+const UNCHARTED2_WHITE: f32 = 11.2;
+const UNCHARTED2_BIAS: f32 = 2.0;
+
+/// the sRGB transfer function, from linear into the encoded value
+pub fn srgb_encode(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        12.92 * c
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+impl ToneMapper {
+    /// map a linear radiance into linear display values in [0, 1]
+    pub fn map(&self, c: Color) -> Color {
+        let c = map_channels(c, |x| x.max(0.0)) * 2f32.powf(self.exposure);
+        let c = match self.operator {
+            Operator::Clamp => c,
+            Operator::Reinhard => map_luminance(c, |l| l / (1.0 + l)),
+            Operator::ExtendedReinhard => {
+                let w2 = self.white.powi(2);
+                map_luminance(c, |l| l * (1.0 + l / w2) / (1.0 + l))
+            }
+            Operator::Aces => map_channels(c, aces),
+            Operator::Uncharted2 => map_channels(c, |x| {
+                uncharted2(UNCHARTED2_BIAS * x) / uncharted2(UNCHARTED2_WHITE)
+            }),
+        };
+        map_channels(c, |x| x.clamp(0.0, 1.0))
+    }
+
+    /// tone map and encode the image into 8-bit sRGB
+    pub fn to_rgb8(&self, image: &Image) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(image.pixels.len() * 3);
+        for col in image.pixels.iter() {
+            let col = self.map(*col);
+            for c in [col.x, col.y, col.z] {
+                buf.push((255.0 * srgb_encode(c) + 0.5) as u8);
+            }
+        }
+        buf
+    }
+}